@@ -1,7 +1,41 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::io::{self, Read};
 use std::ops::Deref;
 
+const WITNESS_SCALE_FACTOR: usize = 4;
+const MAX_BLOCK_WEIGHT: usize = 4_000_000;
+const MAX_BLOCK_SERIALIZED_SIZE: usize = 4_000_000;
+const BYTES_PER_SIGOP: usize = 20;
+const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+const MIN_INPUT_SIZE: usize = 36 + 1 + 4;
+const MIN_OUTPUT_SIZE: usize = 8 + 1;
+const ANTI_FEE_SNIPING_WINDOW: u32 = 100;
+const MAX_STANDARD_TX_WEIGHT: usize = 400_000;
+const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+const MAX_OP_RETURN_RELAY: usize = 83;
+const MAX_SCRIPT_SIZE: usize = 10_000;
+const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+const ROUND_AMOUNT: u64 = 1_000;
+const RELAY_SIZE_SCALE: f64 = 100_000.0;
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_FFFF;
+#[cfg(feature = "hashes")]
+const SIGHASH_NONE: u32 = 0x02;
+#[cfg(feature = "hashes")]
+const SIGHASH_SINGLE: u32 = 0x03;
+#[cfg(feature = "hashes")]
+const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+const PUSH_COST: usize = 1;
+const OP_COST: usize = 2;
+const HASH_COST: usize = 10;
+const SIG_COST: usize = 50;
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct CompactSize {
     pub value: u64,
@@ -11,6 +45,26 @@ pub struct CompactSize {
 pub enum BitcoinError {
     InsufficientBytes,
     InvalidFormat,
+    Io(io::ErrorKind),
+    LimitExceeded,
+    Incomplete,
+}
+
+fn ensure_len(bytes: &[u8], n: usize) -> Result<(), BitcoinError> {
+    if bytes.len() < n {
+        return Err(BitcoinError::InsufficientBytes);
+    }
+    Ok(())
+}
+
+fn read_var_bytes(bytes: &[u8]) -> Result<(Vec<u8>, usize), BitcoinError> {
+    let (len, consumed) = CompactSize::from_bytes(bytes)?;
+    let total = usize::try_from(len.value)
+        .ok()
+        .and_then(|len| consumed.checked_add(len))
+        .ok_or(BitcoinError::InsufficientBytes)?;
+    ensure_len(bytes, total)?;
+    Ok((bytes[consumed..total].to_vec(), total))
 }
 
 impl CompactSize {
@@ -38,15 +92,11 @@ impl CompactSize {
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.is_empty() {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+        ensure_len(bytes, 1)?;
         match bytes[0] {
             n @ 0x00..=0xFC => Ok((CompactSize { value: n as u64 }, 1)),
             0xFD => {
-                if bytes.len() < 3 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
+                ensure_len(bytes, 3)?;
                 let mut arr = [0u8; 2];
                 arr.copy_from_slice(&bytes[1..3]);
                 let v = u16::from_le_bytes(arr) as u64;
@@ -56,9 +106,7 @@ impl CompactSize {
                 Ok((CompactSize { value: v }, 3))
             }
             0xFE => {
-                if bytes.len() < 5 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
+                ensure_len(bytes, 5)?;
                 let mut arr = [0u8; 4];
                 arr.copy_from_slice(&bytes[1..5]);
                 let v = u32::from_le_bytes(arr) as u64;
@@ -68,9 +116,7 @@ impl CompactSize {
                 Ok((CompactSize { value: v }, 5))
             }
             0xFF => {
-                if bytes.len() < 9 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
+                ensure_len(bytes, 9)?;
                 let mut arr = [0u8; 8];
                 arr.copy_from_slice(&bytes[1..9]);
                 let v = u64::from_le_bytes(arr);
@@ -83,7 +129,7 @@ impl CompactSize {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Txid(pub [u8; 32]);
 
 impl Serialize for Txid {
@@ -111,7 +157,7 @@ impl<'de> Deserialize<'de> for Txid {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub struct OutPoint {
     pub txid: Txid,
     pub vout: u32,
@@ -133,9 +179,7 @@ impl OutPoint {
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.len() < 36 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+        ensure_len(bytes, 36)?;
         let mut txid = [0u8; 32];
         txid.copy_from_slice(&bytes[0..32]);
         let mut vout_bytes = [0u8; 4];
@@ -145,6 +189,25 @@ impl OutPoint {
     }
 }
 
+pub fn serialize_outpoints(outpoints: &[OutPoint]) -> Vec<u8> {
+    let mut v = CompactSize::new(outpoints.len() as u64).to_bytes();
+    for outpoint in outpoints {
+        v.extend_from_slice(&outpoint.to_bytes());
+    }
+    v
+}
+
+pub fn parse_outpoints(bytes: &[u8]) -> Result<(Vec<OutPoint>, usize), BitcoinError> {
+    let (count, mut offset) = CompactSize::from_bytes(bytes)?;
+    let mut outpoints = Vec::new();
+    for _ in 0..count.value {
+        let (outpoint, consumed) = OutPoint::from_bytes(&bytes[offset..])?;
+        outpoints.push(outpoint);
+        offset += consumed;
+    }
+    Ok((outpoints, offset))
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Script {
     pub bytes: Vec<u8>,
@@ -162,14 +225,292 @@ impl Script {
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let (len, consumed) = CompactSize::from_bytes(bytes)?;
-        let total = consumed + (len.value as usize);
-        if bytes.len() < total {
-            return Err(BitcoinError::InsufficientBytes);
-        }
-        let script_bytes = bytes[consumed..total].to_vec();
+        let (script_bytes, total) = read_var_bytes(bytes)?;
         Ok((Script::new(script_bytes), total))
     }
+
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions::new(&self.bytes)
+    }
+
+    pub fn script_type(&self) -> ScriptType {
+        let b = &self.bytes[..];
+        match b {
+            [0x21, .., 0xAC] if b.len() == 35 => ScriptType::P2pk,
+            [0x41, .., 0xAC] if b.len() == 67 => ScriptType::P2pk,
+            [0x76, 0xA9, 0x14, .., 0x88, 0xAC] if b.len() == 25 => ScriptType::P2pkh,
+            [0xA9, 0x14, .., 0x87] if b.len() == 23 => ScriptType::P2sh,
+            [0x00, 0x14, ..] if b.len() == 22 => ScriptType::P2wpkh,
+            [0x00, 0x20, ..] if b.len() == 34 => ScriptType::P2wsh,
+            [0x51, 0x20, ..] if b.len() == 34 => ScriptType::P2tr,
+            [0x6A, rest @ ..] if is_push_only(rest) => ScriptType::OpReturn,
+            _ if self.multisig_threshold().is_some() => ScriptType::Multisig,
+            _ => ScriptType::NonStandard,
+        }
+    }
+
+    /// P2TR outputs use BIP-386 `rawtr(...)`, since the key in the script is
+    /// already tweaked and `tr(...)` would tweak it again.
+    pub fn to_descriptor(&self) -> Option<String> {
+        let b = &self.bytes[..];
+        let descriptor = match self.script_type() {
+            ScriptType::P2pk => format!("pk({})", hex::encode(&b[1..b.len() - 1])),
+            ScriptType::P2pkh => format!("pkh({})", hex::encode(&b[3..23])),
+            ScriptType::P2sh => format!("sh({})", hex::encode(&b[2..22])),
+            ScriptType::P2wpkh => format!("wpkh({})", hex::encode(&b[2..])),
+            ScriptType::P2wsh => format!("wsh({})", hex::encode(&b[2..])),
+            ScriptType::P2tr => format!("rawtr({})", hex::encode(&b[2..])),
+            ScriptType::Multisig => {
+                let (m, _) = self.multisig_threshold()?;
+                let keys: Vec<String> = self
+                    .instructions()
+                    .filter_map(|ins| match ins {
+                        Ok(Instruction::Push(key)) => Some(hex::encode(key)),
+                        _ => None,
+                    })
+                    .collect();
+                format!("multi({},{})", m, keys.join(","))
+            }
+            ScriptType::OpReturn | ScriptType::NonStandard => return None,
+        };
+        Some(descriptor)
+    }
+
+    pub fn execution_cost(&self) -> usize {
+        let mut cost = 0;
+        let mut last = None;
+        for ins in self.instructions().map_while(Result::ok) {
+            cost += match ins {
+                Instruction::Push(_) | Instruction::Op(0x4F..=0x60) => PUSH_COST,
+                Instruction::Op(0xA6..=0xAA) => HASH_COST,
+                Instruction::Op(0xAC | 0xAD | 0xBA) => SIG_COST,
+                Instruction::Op(0xAE | 0xAF) => {
+                    let keys = last
+                        .as_ref()
+                        .and_then(decode_small_int)
+                        .map_or(MAX_PUBKEYS_PER_MULTISIG, usize::from);
+                    SIG_COST * keys
+                }
+                Instruction::Op(_) => OP_COST,
+            };
+            last = Some(ins);
+        }
+        cost
+    }
+
+    pub fn sigop_count(&self) -> usize {
+        self.count_sigops(false)
+    }
+
+    pub fn witness_sigops(&self) -> usize {
+        self.count_sigops(true)
+    }
+
+    fn count_sigops(&self, accurate: bool) -> usize {
+        let mut count = 0;
+        let mut last = None;
+        for ins in self.instructions().map_while(Result::ok) {
+            count += match ins {
+                Instruction::Op(0xAC | 0xAD) => 1,
+                Instruction::Op(0xAE | 0xAF) => match last.as_ref().and_then(decode_small_int) {
+                    Some(n) if accurate => usize::from(n),
+                    _ => MAX_PUBKEYS_PER_MULTISIG,
+                },
+                _ => 0,
+            };
+            last = Some(ins);
+        }
+        count
+    }
+
+    pub fn has_valid_der_signatures(&self) -> bool {
+        self.instructions().all(|ins| match ins {
+            Ok(Instruction::Push(data)) if is_signature_push(data) => is_strict_der_signature(data),
+            Ok(_) => true,
+            Err(_) => false,
+        })
+    }
+
+    /// Typical signed scriptSig size for spending `output_type`, assuming 72-byte
+    /// signatures, compressed keys, and P2SH wrapping P2WPKH. Native segwit spends
+    /// use an empty scriptSig; multisig and non-standard spends depend on more
+    /// than the output type and are reported as 0.
+    pub fn spend_scriptsig_size(output_type: ScriptType) -> usize {
+        match output_type {
+            ScriptType::P2pk => 1 + 72,
+            ScriptType::P2pkh => 1 + 72 + 1 + 33,
+            ScriptType::P2sh => 1 + 22,
+            ScriptType::P2wpkh | ScriptType::P2wsh | ScriptType::P2tr => 0,
+            ScriptType::Multisig | ScriptType::OpReturn | ScriptType::NonStandard => 0,
+        }
+    }
+
+    pub fn taproot_output_key(&self) -> Option<[u8; 32]> {
+        if self.script_type() != ScriptType::P2tr {
+            return None;
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&self.bytes[2..]);
+        Some(key)
+    }
+
+    pub fn segwit_scriptcode(&self) -> Option<Script> {
+        if self.script_type() != ScriptType::P2wpkh {
+            return None;
+        }
+        let mut code = vec![0x76, 0xA9, 0x14];
+        code.extend_from_slice(&self.bytes[2..]);
+        code.extend_from_slice(&[0x88, 0xAC]);
+        Some(Script::new(code))
+    }
+
+    pub fn multisig_threshold(&self) -> Option<(u8, u8)> {
+        let ins: Vec<Instruction> = self.instructions().collect::<Result<_, _>>().ok()?;
+        let (first, rest) = ins.split_first()?;
+        let (last, rest) = rest.split_last()?;
+        let (n_op, keys) = rest.split_last()?;
+        let m = decode_small_int(first)?;
+        let n = decode_small_int(n_op)?;
+        if *last != Instruction::Op(0xAE) || m == 0 || m > n || keys.len() != n as usize {
+            return None;
+        }
+        let all_keys = keys
+            .iter()
+            .all(|key| matches!(key, Instruction::Push(k) if k.len() == 33 || k.len() == 65));
+        if !all_keys {
+            return None;
+        }
+        Some((m, n))
+    }
+
+    pub fn cltv_constraint(&self) -> Option<u32> {
+        self.leading_timelock(0xB1)
+    }
+
+    pub fn csv_constraint(&self) -> Option<u32> {
+        self.leading_timelock(0xB2)
+    }
+
+    pub fn is_htlc(&self) -> bool {
+        let Ok(ins) = self.instructions().collect::<Result<Vec<_>, _>>() else {
+            return false;
+        };
+        let hashlock = match ins.as_slice() {
+            [
+                Instruction::Op(0x63),
+                Instruction::Op(op),
+                Instruction::Push(hash),
+                Instruction::Op(0x88),
+                ..,
+            ] => matches!((op, hash.len()), (0xA9, 20) | (0xA8, 32)),
+            _ => false,
+        };
+        let Some(else_pos) = ins.iter().position(|i| *i == Instruction::Op(0x67)) else {
+            return false;
+        };
+        let timelock = match &ins[else_pos + 1..] {
+            [locktime, Instruction::Op(0xB1), ..] => decode_locktime_num(locktime).is_some(),
+            _ => false,
+        };
+        hashlock && timelock && ins[else_pos..].contains(&Instruction::Op(0x68))
+    }
+
+    /// Witness weight of a script-path spend of this tapscript: `sig_count`
+    /// 64-byte Schnorr signatures, the script, and the 33-byte control block of
+    /// a single-leaf tree.
+    pub fn tapscript_spend_weight(&self, sig_count: usize) -> usize {
+        let with_len = |n: usize| CompactSize::new(n as u64).to_bytes().len() + n;
+        let control_block = 33;
+        CompactSize::new(sig_count as u64 + 2).to_bytes().len()
+            + sig_count * with_len(64)
+            + with_len(self.bytes.len())
+            + with_len(control_block)
+    }
+
+    fn leading_timelock(&self, opcode: u8) -> Option<u32> {
+        let mut ins = self.instructions();
+        let value = decode_locktime_num(&ins.next()?.ok()?)?;
+        match (ins.next()?.ok()?, ins.next()?.ok()?) {
+            (Instruction::Op(op), Instruction::Op(0x75)) if op == opcode => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn spend_requirements(&self) -> SpendRequirements {
+        let script_type = self.script_type();
+        let signatures = match script_type {
+            ScriptType::P2pk | ScriptType::P2pkh | ScriptType::P2wpkh | ScriptType::P2tr => 1,
+            ScriptType::Multisig => self.multisig_threshold().map_or(0, |(m, _)| usize::from(m)),
+            ScriptType::NonStandard => self.witness_sigops(),
+            ScriptType::P2sh | ScriptType::P2wsh | ScriptType::OpReturn => 0,
+        };
+        let timelocked = self
+            .instructions()
+            .map_while(Result::ok)
+            .any(|ins| matches!(ins, Instruction::Op(0xB1 | 0xB2)));
+        SpendRequirements {
+            signatures,
+            needs_redeem_script: script_type == ScriptType::P2sh,
+            needs_witness_script: script_type == ScriptType::P2wsh,
+            timelocked,
+            spendable: script_type != ScriptType::OpReturn,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScriptType {
+    P2pk,
+    P2pkh,
+    P2sh,
+    Multisig,
+    OpReturn,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    NonStandard,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Network {
+    Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+#[cfg(feature = "hashes")]
+impl Network {
+    fn p2pkh_prefix(self) -> u8 {
+        match self {
+            Network::Bitcoin => 0x00,
+            _ => 0x6F,
+        }
+    }
+
+    fn p2sh_prefix(self) -> u8 {
+        match self {
+            Network::Bitcoin => 0x05,
+            _ => 0xC4,
+        }
+    }
+
+    fn bech32_hrp(self) -> &'static str {
+        match self {
+            Network::Bitcoin => "bc",
+            Network::Testnet | Network::Signet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SpendRequirements {
+    pub signatures: usize,
+    pub needs_redeem_script: bool,
+    pub needs_witness_script: bool,
+    pub timelocked: bool,
+    pub spendable: bool,
 }
 
 impl Deref for Script {
@@ -179,6 +520,268 @@ impl Deref for Script {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction<'a> {
+    Push(&'a [u8]),
+    Op(u8),
+}
+
+pub struct Instructions<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Instructions<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Instructions { bytes, pos: 0 }
+    }
+
+    fn push_span(rest: &[u8]) -> Result<(usize, usize), BitcoinError> {
+        let header = match rest[0] {
+            0x4C => 2,
+            0x4D => 3,
+            0x4E => 5,
+            _ => 1,
+        };
+        ensure_len(rest, header)?;
+        let len = match rest[0] {
+            0x4C => rest[1] as usize,
+            0x4D => u16::from_le_bytes([rest[1], rest[2]]) as usize,
+            0x4E => u32::from_le_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize,
+            n => n as usize,
+        };
+        ensure_len(rest, header + len)?;
+        Ok((header, header + len))
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, BitcoinError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.bytes[self.pos..];
+        let opcode = *rest.first()?;
+        if opcode > 0x4E {
+            self.pos += 1;
+            return Some(Ok(Instruction::Op(opcode)));
+        }
+        match Self::push_span(rest) {
+            Ok((start, end)) => {
+                self.pos += end;
+                Some(Ok(Instruction::Push(&rest[start..end])))
+            }
+            Err(e) => {
+                self.pos = self.bytes.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn decode_small_int(ins: &Instruction) -> Option<u8> {
+    match *ins {
+        Instruction::Op(op @ 0x51..=0x60) => Some(op - 0x50),
+        _ => None,
+    }
+}
+
+fn decode_locktime_num(ins: &Instruction) -> Option<u32> {
+    let data = match *ins {
+        Instruction::Push(data) if data.len() <= 5 => data,
+        _ => return decode_small_int(ins).map(u32::from),
+    };
+    let Some((&last, _)) = data.split_last() else {
+        return Some(0);
+    };
+    if last & 0x80 != 0 {
+        return None;
+    }
+    let value = data
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &byte| (acc << 8) | u64::from(byte));
+    u32::try_from(value).ok()
+}
+
+fn is_pubkey(data: &[u8]) -> bool {
+    matches!(
+        (data.len(), data.first()),
+        (33, Some(0x02 | 0x03)) | (65, Some(0x04))
+    )
+}
+
+/// The version and program of a segwit output: a version opcode (OP_0 or
+/// OP_1..OP_16) followed by one direct push of 2 to 40 bytes.
+fn witness_program(bytes: &[u8]) -> Option<(u8, &[u8])> {
+    let version = match *bytes.first()? {
+        0x00 => 0,
+        op @ 0x51..=0x60 => op - 0x50,
+        _ => return None,
+    };
+    match bytes.get(1..) {
+        Some([len @ 2..=40, program @ ..]) if program.len() == usize::from(*len) => {
+            Some((version, program))
+        }
+        _ => None,
+    }
+}
+
+fn is_push_only(bytes: &[u8]) -> bool {
+    Instructions::new(bytes).all(|ins| match ins {
+        Ok(Instruction::Push(_)) => true,
+        Ok(Instruction::Op(op)) => op <= 0x60,
+        Err(_) => false,
+    })
+}
+
+#[cfg(feature = "hashes")]
+impl Script {
+    pub fn tapleaf_hash(&self, leaf_version: u8) -> [u8; 32] {
+        let mut data = vec![leaf_version];
+        data.extend_from_slice(&self.to_bytes());
+        tagged_hash("TapLeaf", &data)
+    }
+
+    /// Whether `scriptsig` is push-only and its last push, the redeem script,
+    /// hashes to the 20-byte hash in the P2SH `scriptpubkey`.
+    pub fn p2sh_matches(scriptsig: &Script, scriptpubkey: &Script) -> bool {
+        if scriptpubkey.script_type() != ScriptType::P2sh || !is_push_only(scriptsig) {
+            return false;
+        }
+        match scriptsig.instructions().last() {
+            Some(Ok(Instruction::Push(redeem))) => hash160(redeem) == scriptpubkey[2..22],
+            _ => false,
+        }
+    }
+
+    /// Base58check for P2PKH and P2SH, bech32 for v0 witness programs and
+    /// bech32m for later versions; `None` for scripts without an address form.
+    pub fn to_address(&self, network: Network) -> Option<String> {
+        match self.script_type() {
+            ScriptType::P2pkh => Some(base58check_encode(network.p2pkh_prefix(), &self[3..23])),
+            ScriptType::P2sh => Some(base58check_encode(network.p2sh_prefix(), &self[2..22])),
+            _ => witness_program(self)
+                .map(|(version, program)| segwit_encode(network.bech32_hrp(), version, program)),
+        }
+    }
+}
+
+#[cfg(feature = "secp256k1")]
+impl Script {
+    pub fn has_low_s_signatures(&self) -> bool {
+        self.instructions().all(|ins| match ins {
+            Ok(Instruction::Push(data)) if is_signature_push(data) => {
+                match secp256k1::ecdsa::Signature::from_der(&data[..data.len() - 1]) {
+                    Ok(sig) => {
+                        let mut normalized = sig;
+                        normalized.normalize_s();
+                        normalized == sig
+                    }
+                    Err(_) => true,
+                }
+            }
+            Ok(_) => true,
+            Err(_) => false,
+        })
+    }
+
+    pub fn normalize_low_s(&mut self) {
+        let mut out = Vec::with_capacity(self.bytes.len());
+        let mut iter = self.instructions();
+        loop {
+            let start = iter.pos;
+            match iter.next() {
+                Some(Ok(Instruction::Push(data))) if is_signature_push(data) => {
+                    let (der, sighash) = data.split_at(data.len() - 1);
+                    match secp256k1::ecdsa::Signature::from_der(der) {
+                        Ok(mut sig) => {
+                            sig.normalize_s();
+                            let mut push = sig.serialize_der().to_vec();
+                            push.extend_from_slice(sighash);
+                            push_data(&mut out, &push);
+                        }
+                        Err(_) => out.extend_from_slice(&self.bytes[start..iter.pos]),
+                    }
+                }
+                Some(Ok(_)) => out.extend_from_slice(&self.bytes[start..iter.pos]),
+                Some(Err(_)) => {
+                    out.extend_from_slice(&self.bytes[start..]);
+                    break;
+                }
+                None => break,
+            }
+        }
+        self.bytes = out;
+    }
+}
+
+fn is_signature_push(data: &[u8]) -> bool {
+    (9..=73).contains(&data.len()) && data[0] == 0x30
+}
+
+fn is_strict_der_signature(sig: &[u8]) -> bool {
+    let len = sig.len();
+    if !(9..=73).contains(&len) || sig[0] != 0x30 || sig[1] as usize != len - 3 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= len {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 7 != len {
+        return false;
+    }
+    let r = &sig[4..4 + len_r];
+    let s = &sig[6 + len_r..6 + len_r + len_s];
+    sig[2] == 0x02 && sig[4 + len_r] == 0x02 && is_der_integer(r) && is_der_integer(s)
+}
+
+fn is_der_integer(int: &[u8]) -> bool {
+    match int {
+        [] => false,
+        [first, ..] if first & 0x80 != 0 => false,
+        [0x00, second, ..] => second & 0x80 != 0,
+        _ => true,
+    }
+}
+
+fn push_data(v: &mut Vec<u8>, data: &[u8]) {
+    let len = data.len();
+    if len < 0x4C {
+        v.push(len as u8);
+    } else if len <= 0xFF {
+        v.push(0x4C);
+        v.push(len as u8);
+    } else if len <= 0xFFFF {
+        v.push(0x4D);
+        v.extend_from_slice(&(len as u16).to_le_bytes());
+    } else {
+        v.push(0x4E);
+        v.extend_from_slice(&(len as u32).to_le_bytes());
+    }
+    v.extend_from_slice(data);
+}
+
+/// Weight an input spending `output_type` adds, using the same assumptions as
+/// `Script::spend_scriptsig_size` plus the typical witness for segwit spends.
+/// `None` for P2WSH, bare multisig and non-standard outputs, whose spend size
+/// depends on the script rather than the output type, and for OP_RETURN.
+pub fn input_weight(output_type: ScriptType) -> Option<usize> {
+    let witness = match output_type {
+        ScriptType::P2pk | ScriptType::P2pkh => 0,
+        ScriptType::P2wpkh | ScriptType::P2sh => 1 + (1 + 72) + (1 + 33),
+        ScriptType::P2tr => 1 + (1 + 64),
+        ScriptType::P2wsh
+        | ScriptType::Multisig
+        | ScriptType::OpReturn
+        | ScriptType::NonStandard => return None,
+    };
+    let script_sig = Script::spend_scriptsig_size(output_type);
+    let base = 36 + CompactSize::new(script_sig as u64).to_bytes().len() + script_sig + 4;
+    Some(base * WITNESS_SCALE_FACTOR + witness)
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct TransactionInput {
     pub previous_output: OutPoint,
@@ -206,9 +809,7 @@ impl TransactionInput {
         let (outpoint, consumed1) = OutPoint::from_bytes(bytes)?;
         let (script, consumed2) = Script::from_bytes(&bytes[consumed1..])?;
         let offset = consumed1 + consumed2;
-        if bytes.len() < offset + 4 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+        ensure_len(bytes, offset + 4)?;
         let mut seq_bytes = [0u8; 4];
         seq_bytes.copy_from_slice(&bytes[offset..offset + 4]);
         let sequence = u32::from_le_bytes(seq_bytes);
@@ -217,12 +818,140 @@ impl TransactionInput {
             offset + 4,
         ))
     }
+
+    /// Strictness check beyond consensus: BIP-68 only reads the type flag and
+    /// the low 16 bits of a version 2+ sequence with the disable flag clear and
+    /// ignores the remaining bits, which stay valid. This rejects such
+    /// sequences when any of those reserved bits are set.
+    pub fn validate_sequence(&self, tx_version: u32) -> Result<(), BitcoinError> {
+        if tx_version < 2 || self.sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return Ok(());
+        }
+        let reserved = !(SEQUENCE_LOCKTIME_DISABLE_FLAG
+            | SEQUENCE_LOCKTIME_TYPE_FLAG
+            | SEQUENCE_LOCKTIME_MASK);
+        if self.sequence & reserved != 0 {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct Witness(pub Vec<Vec<u8>>);
+
+impl Witness {
+    pub fn new(items: Vec<Vec<u8>>) -> Self {
+        Witness(items)
+    }
+
+    /// The control block of a taproot script-path spend: the last element once
+    /// a BIP-341 annex (a final element starting with 0x50, when there are at
+    /// least two) is removed. Key-path spends have none.
+    pub fn taproot_control_block(&self) -> Option<&[u8]> {
+        match self.without_annex() {
+            [_, .., control_block] => Some(control_block),
+            _ => None,
+        }
+    }
+
+    /// A single 64-byte Schnorr signature, or 65 bytes with an explicit sighash
+    /// type, once any annex is removed.
+    pub fn is_taproot_keypath(&self) -> bool {
+        matches!(self.without_annex(), [sig] if sig.len() == 64 || sig.len() == 65)
+    }
+
+    /// Each element as hex, like the `txinwitness` array in Bitcoin Core's
+    /// transaction JSON.
+    pub fn to_hex_items(&self) -> Vec<String> {
+        self.0.iter().map(hex::encode).collect()
+    }
+
+    /// Fills empty elements of `self` from the same positions in `other`. Both
+    /// stacks must have the same shape, and non-empty elements must agree;
+    /// otherwise `self` is left unchanged.
+    pub fn merge(&mut self, other: &Witness) -> Result<(), BitcoinError> {
+        if self.0.len() != other.0.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let conflict = self
+            .0
+            .iter()
+            .zip(&other.0)
+            .any(|(ours, theirs)| !ours.is_empty() && !theirs.is_empty() && ours != theirs);
+        if conflict {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        for (ours, theirs) in self.0.iter_mut().zip(&other.0) {
+            if ours.is_empty() {
+                ours.clone_from(theirs);
+            }
+        }
+        Ok(())
+    }
+
+    fn without_annex(&self) -> &[Vec<u8>] {
+        match self.0.as_slice() {
+            [rest @ .., annex] if !rest.is_empty() && annex.first() == Some(&0x50) => rest,
+            items => items,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TransactionOutput {
+    pub value: u64,
+    pub script_pubkey: Script,
+}
+
+impl TransactionOutput {
+    pub fn new(value: u64, script_pubkey: Script) -> Self {
+        TransactionOutput {
+            value,
+            script_pubkey,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = self.value.to_le_bytes().to_vec();
+        v.extend_from_slice(&self.script_pubkey.to_bytes());
+        v
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_len(bytes, 8)?;
+        let mut value_bytes = [0u8; 8];
+        value_bytes.copy_from_slice(&bytes[0..8]);
+        let value = u64::from_le_bytes(value_bytes);
+        let (script_pubkey, consumed) = Script::from_bytes(&bytes[8..])?;
+        Ok((TransactionOutput::new(value, script_pubkey), 8 + consumed))
+    }
+
+    /// Bitcoin Core's dust rule: the output is worth less than the fee, at
+    /// `dust_relay_fee` sat/kvB rounded up, of creating and later spending it.
+    /// Any witness program gets the discounted spend size, and provably
+    /// unspendable scripts (starting with OP_RETURN, or over 10,000 bytes) are
+    /// never dust.
+    pub fn is_dust(&self, dust_relay_fee: u64) -> bool {
+        let script = &self.script_pubkey;
+        if script.first() == Some(&0x6A) || script.len() > MAX_SCRIPT_SIZE {
+            return false;
+        }
+        let spend_size = match witness_program(script) {
+            Some(_) => 32 + 4 + 1 + 107 / 4 + 4,
+            None => 32 + 4 + 1 + 107 + 4,
+        };
+        let size = (self.to_bytes().len() + spend_size) as u64;
+        self.value < size.saturating_mul(dust_relay_fee).div_ceil(1000)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct BitcoinTransaction {
     pub version: u32,
     pub inputs: Vec<TransactionInput>,
+    #[serde(default)]
+    pub outputs: Vec<TransactionOutput>,
     pub lock_time: u32,
 }
 
@@ -231,6 +960,7 @@ impl BitcoinTransaction {
         BitcoinTransaction {
             version,
             inputs,
+            outputs: Vec::new(),
             lock_time,
         }
     }
@@ -241,45 +971,1647 @@ impl BitcoinTransaction {
         for input in &self.inputs {
             v.extend_from_slice(&input.to_bytes());
         }
+        v.extend_from_slice(&CompactSize::new(self.outputs.len() as u64).to_bytes());
+        for output in &self.outputs {
+            v.extend_from_slice(&output.to_bytes());
+        }
         v.extend_from_slice(&self.lock_time.to_le_bytes());
         v
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.len() < 4 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+        ensure_len(bytes, 4)?;
         let mut version_bytes = [0u8; 4];
         version_bytes.copy_from_slice(&bytes[0..4]);
         let version = u32::from_le_bytes(version_bytes);
         let (input_count, consumed1) = CompactSize::from_bytes(&bytes[4..])?;
         let mut offset = 4 + consumed1;
-        let mut inputs = Vec::with_capacity(input_count.value as usize);
+        let max_inputs = (bytes.len() - offset) / MIN_INPUT_SIZE;
+        let mut inputs = Vec::with_capacity(input_count.value.min(max_inputs as u64) as usize);
         for _ in 0..input_count.value {
             let (input, consumed) = TransactionInput::from_bytes(&bytes[offset..])?;
             inputs.push(input);
             offset += consumed;
         }
-        if bytes.len() < offset + 4 {
-            return Err(BitcoinError::InsufficientBytes);
+        let (output_count, consumed) = CompactSize::from_bytes(&bytes[offset..])?;
+        offset += consumed;
+        let max_outputs = (bytes.len() - offset) / MIN_OUTPUT_SIZE;
+        let mut outputs = Vec::with_capacity(output_count.value.min(max_outputs as u64) as usize);
+        for _ in 0..output_count.value {
+            let (output, consumed) = TransactionOutput::from_bytes(&bytes[offset..])?;
+            outputs.push(output);
+            offset += consumed;
         }
+        ensure_len(bytes, offset + 4)?;
         let mut lock_time_bytes = [0u8; 4];
         lock_time_bytes.copy_from_slice(&bytes[offset..offset + 4]);
         let lock_time = u32::from_le_bytes(lock_time_bytes);
         Ok((
-            BitcoinTransaction::new(version, inputs, lock_time),
+            BitcoinTransaction {
+                version,
+                inputs,
+                outputs,
+                lock_time,
+            },
             offset + 4,
         ))
     }
-}
 
-impl fmt::Display for BitcoinTransaction {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Version: {}", self.version)?;
-        writeln!(f, "Lock Time: {}", self.lock_time)?;
-        for input in &self.inputs {
-            writeln!(f, "Previous Output Vout: {}", input.previous_output.vout)?;
+    pub fn from_bytes_at(bytes: &[u8], offset: usize) -> Result<(Self, usize), BitcoinError> {
+        ensure_len(bytes, offset)?;
+        let (tx, consumed) = BitcoinTransaction::from_bytes(&bytes[offset..])?;
+        Ok((tx, offset + consumed))
+    }
+
+    pub fn weight(&self) -> usize {
+        self.to_bytes().len() * WITNESS_SCALE_FACTOR
+    }
+
+    pub fn exceeds_max_weight(&self) -> bool {
+        self.weight() > MAX_BLOCK_WEIGHT
+    }
+
+    pub fn fee_rate_sat_per_kwu(&self, fee: u64) -> f64 {
+        fee as f64 * 1000.0 / self.weight() as f64
+    }
+
+    pub fn is_timelocked(&self) -> bool {
+        self.lock_time != 0 && self.inputs.iter().any(|input| input.sequence < 0xFFFFFFFF)
+    }
+
+    /// Time-based lock times are compared against the median-time-past of the
+    /// previous 11 blocks (BIP-113), not the block header timestamp.
+    pub fn is_final_at(&self, height: u32, mtp: u32) -> bool {
+        if self.lock_time == 0 {
+            return true;
         }
-        Ok(())
+        let cutoff = if self.lock_time < LOCKTIME_THRESHOLD {
+            height
+        } else {
+            mtp
+        };
+        self.lock_time < cutoff || self.inputs.iter().all(|input| input.sequence == 0xFFFFFFFF)
+    }
+
+    /// Compact JSON with object keys sorted at every level and no whitespace.
+    /// Keys are sorted here rather than left to `serde_json::Map`, whose order
+    /// changes if any crate enables serde_json's `preserve_order` feature.
+    /// Integers are plain decimal numbers, txids are hex strings in internal
+    /// byte order, and scripts are `{"bytes":[..]}` arrays of byte values.
+    pub fn canonical_json(&self) -> String {
+        let value =
+            serde_json::to_value(self).expect("transaction fields always serialize to JSON");
+        let mut out = String::new();
+        write_sorted_json(&value, &mut out);
+        out
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.vsize_ceil()
+    }
+
+    /// Virtual size with Bitcoin Core's rounding,
+    /// `(weight + WITNESS_SCALE_FACTOR - 1) / WITNESS_SCALE_FACTOR`, rounding up
+    /// rather than down.
+    pub fn vsize_ceil(&self) -> usize {
+        self.weight().div_ceil(WITNESS_SCALE_FACTOR)
+    }
+
+    pub fn sigop_count(&self) -> usize {
+        let input_sigops: usize = self
+            .inputs
+            .iter()
+            .map(|input| input.script_sig.sigop_count())
+            .sum();
+        let output_sigops: usize = self
+            .outputs
+            .iter()
+            .map(|output| output.script_pubkey.sigop_count())
+            .sum();
+        input_sigops + output_sigops
+    }
+
+    pub fn billable_size(&self) -> usize {
+        self.vsize().max(self.sigop_count() * BYTES_PER_SIGOP)
+    }
+
+    pub fn referenced_pubkeys(&self, prevout_scripts: &[Script]) -> Vec<Vec<u8>> {
+        let mut keys: Vec<Vec<u8>> = Vec::new();
+        let mut collect = |bytes: &[u8]| {
+            for ins in Instructions::new(bytes).map_while(Result::ok) {
+                if let Instruction::Push(data) = ins
+                    && is_pubkey(data)
+                    && !keys.iter().any(|k| k == data)
+                {
+                    keys.push(data.to_vec());
+                }
+            }
+        };
+        for (i, input) in self.inputs.iter().enumerate() {
+            collect(&input.script_sig);
+            let Some(prevout) = prevout_scripts.get(i) else {
+                continue;
+            };
+            collect(prevout);
+            if prevout.script_type() == ScriptType::P2sh {
+                let redeem = input.script_sig.instructions().map_while(Result::ok).last();
+                if let Some(Instruction::Push(redeem)) = redeem {
+                    collect(redeem);
+                }
+            }
+        }
+        keys
+    }
+
+    pub fn is_fully_segwit(&self, prevout_scripts: &[Script]) -> bool {
+        !self.inputs.is_empty()
+            && prevout_scripts.len() == self.inputs.len()
+            && prevout_scripts.iter().all(|script| {
+                matches!(
+                    script.script_type(),
+                    ScriptType::P2wpkh | ScriptType::P2wsh | ScriptType::P2tr
+                )
+            })
     }
+
+    pub fn with_all_sequences(&self, sequence: u32) -> BitcoinTransaction {
+        let mut tx = self.clone();
+        for input in &mut tx.inputs {
+            input.sequence = sequence;
+        }
+        tx
+    }
+
+    pub fn input_type_uniformity(&self, prevout_scripts: &[Script]) -> bool {
+        prevout_scripts.len() == self.inputs.len()
+            && prevout_scripts
+                .windows(2)
+                .all(|pair| pair[0].script_type() == pair[1].script_type())
+    }
+
+    pub fn uses_antifeesnipe_locktime(&self, current_height: u32) -> bool {
+        self.lock_time != 0
+            && self.lock_time < LOCKTIME_THRESHOLD
+            && self.lock_time <= current_height
+            && current_height - self.lock_time <= ANTI_FEE_SNIPING_WINDOW
+    }
+
+    /// `None` when `input_weight` can't size a spend of `input_type`. The
+    /// transaction is serialized without witnesses, so a segwit input also
+    /// pays for the 2 WU marker and flag and a 1 WU empty witness for each
+    /// existing input.
+    pub fn size_with_additional_input(&self, input_type: ScriptType) -> Option<usize> {
+        let count = self.inputs.len() as u64;
+        let count_growth =
+            CompactSize::new(count + 1).to_bytes().len() - CompactSize::new(count).to_bytes().len();
+        let segwit_overhead = match input_type {
+            ScriptType::P2wpkh | ScriptType::P2sh | ScriptType::P2tr => 2 + self.inputs.len(),
+            _ => 0,
+        };
+        let weight = self.weight()
+            + count_growth * WITNESS_SCALE_FACTOR
+            + segwit_overhead
+            + input_weight(input_type)?;
+        Some(weight.div_ceil(WITNESS_SCALE_FACTOR))
+    }
+
+    /// Relay ordering score: `fee / vsize * 100_000 / (100_000 + vsize)`, the
+    /// fee rate discounted slightly as transactions grow, or 0.0 when the
+    /// transaction fails the local standardness checks (version 1 to 3, weight
+    /// at most 400,000, push-only scriptSigs of at most 1,650 bytes).
+    pub fn relay_priority(&self, fee: u64) -> f64 {
+        let standard = matches!(self.version, 1..=3)
+            && self.weight() <= MAX_STANDARD_TX_WEIGHT
+            && self.inputs.iter().all(|input| {
+                input.script_sig.len() <= MAX_STANDARD_SCRIPTSIG_SIZE
+                    && is_push_only(&input.script_sig)
+            });
+        if !standard {
+            return 0.0;
+        }
+        let vsize = self.vsize() as f64;
+        fee as f64 / vsize * RELAY_SIZE_SCALE / (RELAY_SIZE_SCALE + vsize)
+    }
+
+    pub fn output_script_bytes_total(&self) -> usize {
+        self.outputs
+            .iter()
+            .map(|output| output.script_pubkey.bytes.len())
+            .sum()
+    }
+
+    pub fn simple(inputs: Vec<(OutPoint, u32)>, outputs: Vec<(u64, Script)>) -> BitcoinTransaction {
+        BitcoinTransaction {
+            version: 2,
+            inputs: inputs
+                .into_iter()
+                .map(|(outpoint, sequence)| {
+                    TransactionInput::new(outpoint, Script::new(Vec::new()), sequence)
+                })
+                .collect(),
+            outputs: outputs
+                .into_iter()
+                .map(|(value, script)| TransactionOutput::new(value, script))
+                .collect(),
+            lock_time: 0,
+        }
+    }
+
+    pub fn has_reused_output_scripts(&self) -> bool {
+        self.outputs.iter().enumerate().any(|(i, output)| {
+            self.outputs[i + 1..]
+                .iter()
+                .any(|other| other.script_pubkey == output.script_pubkey)
+        })
+    }
+
+    pub fn equal_output_groups(&self) -> Vec<(u64, usize)> {
+        let mut groups: Vec<(u64, usize)> = Vec::new();
+        for output in &self.outputs {
+            match groups.iter_mut().find(|(value, _)| *value == output.value) {
+                Some((_, count)) => *count += 1,
+                None => groups.push((output.value, 1)),
+            }
+        }
+        groups
+    }
+
+    /// Heuristic classification by input/output counts, where "many" means
+    /// three or more: at least three equal-value outputs and as many inputs is a
+    /// CoinJoin, one or two inputs paying two outputs is a payment, many inputs
+    /// into one output is a consolidation, and one or two inputs paying many
+    /// outputs is a batch.
+    pub fn tx_pattern(&self) -> TxPattern {
+        let (ins, outs) = (self.inputs.len(), self.outputs.len());
+        let largest_group = self.largest_equal_output_group();
+        match (ins, outs) {
+            _ if largest_group >= 3 && ins >= largest_group => TxPattern::CoinJoin,
+            (1..=2, 2) => TxPattern::Payment,
+            (3.., 1) => TxPattern::Consolidation,
+            (1..=2, 3..) => TxPattern::Batch,
+            _ => TxPattern::Other,
+        }
+    }
+
+    pub fn input_output_script_overlap(&self, prevout_scripts: &[Script]) -> bool {
+        self.outputs
+            .iter()
+            .any(|output| prevout_scripts.contains(&output.script_pubkey))
+    }
+
+    pub fn has_nonstandard_outputs(&self) -> bool {
+        self.outputs
+            .iter()
+            .any(|output| match output.script_pubkey.script_type() {
+                ScriptType::NonStandard | ScriptType::Multisig => true,
+                ScriptType::OpReturn => output.script_pubkey.len() > MAX_OP_RETURN_RELAY,
+                _ => false,
+            })
+    }
+
+    pub fn dust_outputs(&self, dust_relay_fee: u64) -> Vec<usize> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.is_dust(dust_relay_fee))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Output total minus the fee at `fee_rate_sat_per_vb`, saturating at the
+    /// `i64` bounds instead of overflowing.
+    pub fn effective_value(&self, fee_rate_sat_per_vb: u64) -> i64 {
+        let total: i128 = self
+            .outputs
+            .iter()
+            .map(|output| i128::from(output.value))
+            .sum();
+        let fee = self.vsize() as i128 * i128::from(fee_rate_sat_per_vb);
+        (total - fee).clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64
+    }
+
+    /// Heuristic, and easily fooled either way: a payjoin looks like a
+    /// multi-input payment with one output to the sender's known change script,
+    /// where the other output is not the round amount a plain payment would
+    /// usually carry because the receiver's own input was added to it.
+    pub fn looks_like_payjoin(&self, known_change_scripts: &[Script]) -> bool {
+        if self.inputs.len() < 2 || self.outputs.len() != 2 {
+            return false;
+        }
+        let (change, payment): (Vec<_>, Vec<_>) = self
+            .outputs
+            .iter()
+            .partition(|output| known_change_scripts.contains(&output.script_pubkey));
+        matches!(
+            (change.as_slice(), payment.as_slice()),
+            ([_], [payment]) if payment.value % ROUND_AMOUNT != 0
+        )
+    }
+
+    /// Heuristic that can easily be wrong: when every output but one carries a
+    /// round amount (a multiple of 1,000 sat), the one with the odd value is
+    /// guessed to be change, since payments are usually round and change
+    /// absorbs the leftover precision.
+    pub fn likely_change_output(&self) -> Option<usize> {
+        if self.outputs.len() < 2 {
+            return None;
+        }
+        let mut odd = self
+            .outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.value % ROUND_AMOUNT != 0);
+        match (odd.next(), odd.next()) {
+            (Some((i, _)), None) => Some(i),
+            _ => None,
+        }
+    }
+
+    fn largest_equal_output_group(&self) -> usize {
+        self.equal_output_groups()
+            .iter()
+            .map(|&(_, count)| count)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn is_likely_coinjoin(&self, min_participants: usize) -> bool {
+        let largest_group = self.largest_equal_output_group();
+        min_participants > 0
+            && largest_group >= min_participants
+            && self.inputs.len() >= min_participants
+    }
+
+    pub fn net_effect_for(
+        &self,
+        watched: &HashSet<Vec<u8>>,
+        prevouts: &[TransactionOutput],
+    ) -> i64 {
+        let is_watched =
+            |output: &&TransactionOutput| watched.contains(&output.script_pubkey.bytes);
+        let received: u64 = self
+            .outputs
+            .iter()
+            .filter(is_watched)
+            .map(|o| o.value)
+            .sum();
+        let spent: u64 = prevouts.iter().filter(is_watched).map(|o| o.value).sum();
+        received as i64 - spent as i64
+    }
+
+    pub fn deterministic(seed: u64, num_inputs: usize, num_outputs: usize) -> BitcoinTransaction {
+        // SplitMix64, so fixtures stay identical across platforms and releases.
+        let mut state = seed;
+        let mut next = move || {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+        let inputs = (0..num_inputs)
+            .map(|_| {
+                let mut txid = [0u8; 32];
+                for chunk in txid.chunks_mut(8) {
+                    chunk.copy_from_slice(&next().to_le_bytes());
+                }
+                let outpoint = OutPoint::new(txid, (next() % 4) as u32);
+                TransactionInput::new(outpoint, Script::new(Vec::new()), 0xFFFFFFFD)
+            })
+            .collect();
+        let outputs = (0..num_outputs)
+            .map(|_| {
+                let value = next() % 100_000_000;
+                let mut script = vec![0x00, 0x14];
+                script.extend_from_slice(&next().to_le_bytes());
+                script.extend_from_slice(&next().to_le_bytes());
+                script.extend_from_slice(&next().to_le_bytes()[..4]);
+                TransactionOutput::new(value, Script::new(script))
+            })
+            .collect();
+        BitcoinTransaction {
+            version: 2,
+            inputs,
+            outputs,
+            lock_time: 0,
+        }
+    }
+
+    pub fn check_basic(&self) -> Result<(), BitcoinError> {
+        if self.inputs.is_empty() || self.outputs.is_empty() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        if self.weight() > MAX_BLOCK_WEIGHT {
+            return Err(BitcoinError::LimitExceeded);
+        }
+        Ok(())
+    }
+
+    pub fn check_no_duplicate_inputs(&self) -> Result<(), BitcoinError> {
+        let mut seen = HashSet::with_capacity(self.inputs.len());
+        if self
+            .inputs
+            .iter()
+            .all(|input| seen.insert(&input.previous_output))
+        {
+            Ok(())
+        } else {
+            Err(BitcoinError::InvalidFormat)
+        }
+    }
+
+    /// Each output value, and their running total, must be at most 21 million
+    /// BTC.
+    pub fn check_money_range(&self) -> Result<(), BitcoinError> {
+        let mut total: u64 = 0;
+        for output in &self.outputs {
+            total = total
+                .checked_add(output.value)
+                .filter(|&total| output.value <= MAX_MONEY && total <= MAX_MONEY)
+                .ok_or(BitcoinError::LimitExceeded)?;
+        }
+        Ok(())
+    }
+
+    /// The exact bytes hashed for the txid: the base serialization, with no
+    /// segwit marker, flag or witnesses.
+    pub fn for_txid_serialization(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
+
+fn write_sorted_json(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_sorted_json(value, out);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_sorted_json(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TxPattern {
+    Payment,
+    Consolidation,
+    Batch,
+    CoinJoin,
+    Other,
+}
+
+pub fn package_fee_rate(txs: &[BitcoinTransaction], fees: &[u64]) -> Result<f64, BitcoinError> {
+    if txs.is_empty() || txs.len() != fees.len() {
+        return Err(BitcoinError::InvalidFormat);
+    }
+    let total_fee: u64 = fees.iter().sum();
+    let total_vsize: usize = txs.iter().map(BitcoinTransaction::vsize).sum();
+    Ok(total_fee as f64 / total_vsize as f64)
+}
+
+/// Runs `check_basic`, `check_no_duplicate_inputs` and `check_money_range` on
+/// each transaction, reporting the first failure for each.
+pub fn validate_many(txs: &[BitcoinTransaction]) -> Vec<Result<(), BitcoinError>> {
+    txs.iter()
+        .map(|tx| {
+            tx.check_basic()?;
+            tx.check_no_duplicate_inputs()?;
+            tx.check_money_range()
+        })
+        .collect()
+}
+
+/// With the `hashes` feature, each descendant must spend an output of the
+/// parent or of an earlier descendant, or `InvalidFormat` is returned. Without
+/// it no txids are available, and the caller must guarantee that linkage.
+pub fn check_descendant_limits(
+    parent: &BitcoinTransaction,
+    descendants: &[BitcoinTransaction],
+    max_count: usize,
+    max_size: usize,
+) -> Result<(), BitcoinError> {
+    #[cfg(feature = "hashes")]
+    {
+        let mut package = HashSet::from([parent.txid()]);
+        for tx in descendants {
+            let linked = tx
+                .inputs
+                .iter()
+                .any(|input| package.contains(&input.previous_output.txid));
+            if !linked {
+                return Err(BitcoinError::InvalidFormat);
+            }
+            package.insert(tx.txid());
+        }
+    }
+    let size = parent.vsize() + descendants.iter().map(|tx| tx.vsize()).sum::<usize>();
+    if descendants.len() + 1 > max_count || size > max_size {
+        return Err(BitcoinError::LimitExceeded);
+    }
+    Ok(())
+}
+
+pub fn cluster_by_common_input_ownership(txs: &[BitcoinTransaction]) -> Vec<Vec<usize>> {
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let mut parent: Vec<usize> = (0..txs.len()).collect();
+    let mut first_spender: HashMap<&OutPoint, usize> = HashMap::new();
+    for (i, tx) in txs.iter().enumerate() {
+        for input in &tx.inputs {
+            let j = *first_spender.entry(&input.previous_output).or_insert(i);
+            let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+            parent[a.max(b)] = a.min(b);
+        }
+    }
+
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    let mut cluster_of: HashMap<usize, usize> = HashMap::new();
+    for i in 0..txs.len() {
+        let root = find(&mut parent, i);
+        let idx = *cluster_of.entry(root).or_insert_with(|| {
+            clusters.push(Vec::new());
+            clusters.len() - 1
+        });
+        clusters[idx].push(i);
+    }
+    clusters
+}
+
+pub fn detect_nonce_reuse(txs: &[BitcoinTransaction]) -> Vec<(OutPoint, OutPoint)> {
+    let mut first_seen: HashMap<Vec<u8>, &OutPoint> = HashMap::new();
+    let mut reused = Vec::new();
+    for input in txs.iter().flat_map(|tx| &tx.inputs) {
+        let sigs = input
+            .script_sig
+            .instructions()
+            .map_while(Result::ok)
+            .filter_map(|ins| match ins {
+                Instruction::Push(data) if is_strict_der_signature(data) => Some(data),
+                _ => None,
+            });
+        for sig in sigs {
+            let r = sig[4..4 + sig[3] as usize].to_vec();
+            let first = *first_seen.entry(r).or_insert(&input.previous_output);
+            if *first != input.previous_output {
+                reused.push((first.clone(), input.previous_output.clone()));
+            }
+        }
+    }
+    reused
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MempoolEntry {
+    pub tx: BitcoinTransaction,
+    pub fee: u64,
+    pub time_received: u64,
+    pub fee_rate: f64,
+}
+
+impl MempoolEntry {
+    pub fn new(tx: BitcoinTransaction, fee: u64, time_received: u64) -> Self {
+        let fee_rate = fee as f64 / tx.vsize() as f64;
+        MempoolEntry {
+            tx,
+            fee,
+            time_received,
+            fee_rate,
+        }
+    }
+
+    pub fn eviction_cmp(&self, other: &MempoolEntry) -> Ordering {
+        self.fee_rate
+            .total_cmp(&other.fee_rate)
+            .then(self.time_received.cmp(&other.time_received))
+    }
+}
+
+impl fmt::Display for BitcoinTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Lock Time: {}", self.lock_time)?;
+        for input in &self.inputs {
+            writeln!(f, "Previous Output Vout: {}", input.previous_output.vout)?;
+        }
+        for output in &self.outputs {
+            writeln!(f, "Output Value: {}", output.value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "hashes")]
+impl BitcoinTransaction {
+    pub fn txid(&self) -> Txid {
+        Txid(sha256d(&self.for_txid_serialization()))
+    }
+
+    /// The txid, under a name for callers choosing a database key: it hashes
+    /// only the non-witness serialization, so unlike a wtxid it does not change
+    /// when a third party malleates the witness.
+    pub fn stable_id(&self) -> [u8; 32] {
+        self.txid().0
+    }
+
+    pub fn fingerprint(&self) -> String {
+        let mut txid = self.txid().0;
+        txid.reverse();
+        hex::encode(&txid[..4])
+    }
+
+    pub fn cpfp_child(
+        &self,
+        parent_output_index: u32,
+        parent_output: &TransactionOutput,
+        fee: u64,
+        change_script: Script,
+    ) -> Result<BitcoinTransaction, BitcoinError> {
+        if self.outputs.get(parent_output_index as usize) != Some(parent_output)
+            || fee >= parent_output.value
+        {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(BitcoinTransaction::simple(
+            vec![(
+                OutPoint::new(self.txid().0, parent_output_index),
+                0xFFFFFFFD,
+            )],
+            vec![(parent_output.value - fee, change_script)],
+        ))
+    }
+
+    /// Pre-segwit signature hash of input `input_index` signing `script_code`,
+    /// with OP_CODESEPARATORs removed. SIGHASH_SINGLE with no output at
+    /// `input_index` reproduces the consensus bug and returns the constant
+    /// 1 (as a little-endian uint256) instead of an error.
+    pub fn sighash_legacy(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: u32,
+    ) -> Result<[u8; 32], BitcoinError> {
+        if input_index >= self.inputs.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let base_type = sighash_type & 0x1F;
+        if base_type == SIGHASH_SINGLE && input_index >= self.outputs.len() {
+            let mut one = [0u8; 32];
+            one[0] = 1;
+            return Ok(one);
+        }
+        let mut code = Vec::with_capacity(script_code.len());
+        let mut iter = script_code.instructions();
+        loop {
+            let start = iter.pos;
+            match iter.next() {
+                Some(Ok(Instruction::Op(0xAB))) => {}
+                Some(Ok(_)) => code.extend_from_slice(&script_code[start..iter.pos]),
+                Some(Err(_)) => {
+                    code.extend_from_slice(&script_code[start..]);
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        let mut tx = self.clone();
+        for (i, input) in tx.inputs.iter_mut().enumerate() {
+            if i == input_index {
+                input.script_sig = Script::new(code.clone());
+            } else {
+                input.script_sig = Script::new(Vec::new());
+                if base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE {
+                    input.sequence = 0;
+                }
+            }
+        }
+        if sighash_type & SIGHASH_ANYONECANPAY != 0 {
+            tx.inputs = vec![tx.inputs.swap_remove(input_index)];
+        }
+        match base_type {
+            SIGHASH_NONE => tx.outputs.clear(),
+            SIGHASH_SINGLE => {
+                tx.outputs.truncate(input_index + 1);
+                for output in &mut tx.outputs[..input_index] {
+                    *output = TransactionOutput::new(u64::MAX, Script::new(Vec::new()));
+                }
+            }
+            _ => {}
+        }
+        let mut data = tx.to_bytes();
+        data.extend_from_slice(&sighash_type.to_le_bytes());
+        Ok(sha256d(&data))
+    }
+
+    /// BIP-341 signature hash of input `input_index`. `prevouts` holds the
+    /// output spent by every input, in input order. `leaf_hash` selects a
+    /// script-path spend of that tapleaf with no OP_CODESEPARATOR executed;
+    /// `annex` must include its 0x50 prefix.
+    pub fn sighash_taproot(
+        &self,
+        input_index: usize,
+        prevouts: &[TransactionOutput],
+        sighash_type: u8,
+        annex: Option<&[u8]>,
+        leaf_hash: Option<[u8; 32]>,
+    ) -> Result<[u8; 32], BitcoinError> {
+        let anyone_can_pay = u32::from(sighash_type) & SIGHASH_ANYONECANPAY != 0;
+        let base_type = u32::from(sighash_type) & 0x03;
+        if !matches!(sighash_type, 0x00..=0x03 | 0x81..=0x83)
+            || input_index >= self.inputs.len()
+            || prevouts.len() != self.inputs.len()
+            || annex.is_some_and(|annex| annex.first() != Some(&0x50))
+            || (base_type == SIGHASH_SINGLE && input_index >= self.outputs.len())
+        {
+            return Err(BitcoinError::InvalidFormat);
+        }
+
+        let mut msg = vec![0x00, sighash_type];
+        msg.extend_from_slice(&self.version.to_le_bytes());
+        msg.extend_from_slice(&self.lock_time.to_le_bytes());
+        if !anyone_can_pay {
+            let inputs = &self.inputs;
+            msg.extend_from_slice(&sha256_concat(
+                inputs.iter().map(|i| i.previous_output.to_bytes()),
+            ));
+            msg.extend_from_slice(&sha256_concat(
+                prevouts.iter().map(|p| p.value.to_le_bytes().to_vec()),
+            ));
+            msg.extend_from_slice(&sha256_concat(
+                prevouts.iter().map(|p| p.script_pubkey.to_bytes()),
+            ));
+            msg.extend_from_slice(&sha256_concat(
+                inputs.iter().map(|i| i.sequence.to_le_bytes().to_vec()),
+            ));
+        }
+        if base_type != SIGHASH_NONE && base_type != SIGHASH_SINGLE {
+            msg.extend_from_slice(&sha256_concat(self.outputs.iter().map(|o| o.to_bytes())));
+        }
+        msg.push(u8::from(leaf_hash.is_some()) * 2 + u8::from(annex.is_some()));
+        if anyone_can_pay {
+            let input = &self.inputs[input_index];
+            msg.extend_from_slice(&input.previous_output.to_bytes());
+            msg.extend_from_slice(&prevouts[input_index].to_bytes());
+            msg.extend_from_slice(&input.sequence.to_le_bytes());
+        } else {
+            msg.extend_from_slice(&(input_index as u32).to_le_bytes());
+        }
+        if let Some(annex) = annex {
+            msg.extend_from_slice(&sha256_concat([
+                CompactSize::new(annex.len() as u64).to_bytes(),
+                annex.to_vec(),
+            ]));
+        }
+        if base_type == SIGHASH_SINGLE {
+            msg.extend_from_slice(&sha256_concat([self.outputs[input_index].to_bytes()]));
+        }
+        if let Some(leaf_hash) = leaf_hash {
+            msg.extend_from_slice(&leaf_hash);
+            msg.push(0x00);
+            msg.extend_from_slice(&u32::MAX.to_le_bytes());
+        }
+        Ok(tagged_hash("TapSighash", &msg))
+    }
+
+    pub fn content_hash(&self) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+        let mut canonical = self.clone();
+        canonical.inputs.sort_by_key(TransactionInput::to_bytes);
+        canonical.outputs.sort_by_key(TransactionOutput::to_bytes);
+        Sha256::digest(canonical.to_bytes()).into()
+    }
+
+    /// Plain-English summary, one line per fact: the inputs (and their total
+    /// when `prevouts` holds the output spent by each input), every output
+    /// with its address on `network`, the `likely_change_output` guess marked
+    /// as change, and the fee when the inputs are known.
+    pub fn explain(&self, prevouts: Option<&[TransactionOutput]>, network: Network) -> String {
+        let input_total: Option<u128> = prevouts
+            .filter(|prevouts| prevouts.len() == self.inputs.len())
+            .map(|prevouts| prevouts.iter().map(|p| u128::from(p.value)).sum());
+        let output_total: u128 = self.outputs.iter().map(|o| u128::from(o.value)).sum();
+        let inputs = match self.inputs.len() {
+            1 => "1 input".to_string(),
+            n => format!("{} inputs", n),
+        };
+
+        let mut lines = vec![match input_total {
+            Some(total) => format!("Spends {} totaling {}", inputs, format_btc(total)),
+            None => format!("Spends {}", inputs),
+        }];
+        let change = self.likely_change_output();
+        for (i, output) in self.outputs.iter().enumerate() {
+            let script = &output.script_pubkey;
+            let destination = match script.to_address(network) {
+                Some(address) => address,
+                None if script.first() == Some(&0x6A) => "an OP_RETURN data output".to_string(),
+                None => format!("script {}", hex::encode(&script.bytes)),
+            };
+            let note = if change == Some(i) {
+                " (likely change)"
+            } else {
+                ""
+            };
+            lines.push(format!(
+                "Pays {} to {}{}",
+                format_btc(u128::from(output.value)),
+                destination,
+                note
+            ));
+        }
+        match input_total.map(|total| total.checked_sub(output_total)) {
+            Some(Some(fee)) => lines.push(format!(
+                "Fee: {} sat ({:.2} sat/vB)",
+                fee,
+                fee as f64 / self.vsize() as f64
+            )),
+            Some(None) => lines.push("Outputs exceed inputs, so this spend is invalid".to_string()),
+            None => {}
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(feature = "hashes")]
+fn format_btc(sats: u128) -> String {
+    format!("{}.{:08} BTC", sats / 100_000_000, sats % 100_000_000)
+}
+
+#[cfg(feature = "hashes")]
+#[derive(Debug, Clone, Default)]
+pub struct TxHasher {
+    engine: sha2::Sha256,
+}
+
+#[cfg(feature = "hashes")]
+impl TxHasher {
+    pub fn new() -> Self {
+        TxHasher::default()
+    }
+
+    pub fn update_version(&mut self, version: u32) {
+        self.update(&version.to_le_bytes());
+    }
+
+    pub fn update_input_count(&mut self, count: usize) {
+        self.update(&CompactSize::new(count as u64).to_bytes());
+    }
+
+    pub fn update_input(&mut self, input: &TransactionInput) {
+        self.update(&input.to_bytes());
+    }
+
+    pub fn update_output_count(&mut self, count: usize) {
+        self.update(&CompactSize::new(count as u64).to_bytes());
+    }
+
+    pub fn update_output(&mut self, output: &TransactionOutput) {
+        self.update(&output.to_bytes());
+    }
+
+    pub fn update_lock_time(&mut self, lock_time: u32) {
+        self.update(&lock_time.to_le_bytes());
+    }
+
+    pub fn finalize(self) -> Txid {
+        use sha2::{Digest, Sha256};
+        Txid(Sha256::digest(self.engine.finalize()).into())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+        self.engine.update(data);
+    }
+}
+
+#[cfg(feature = "hashes")]
+pub fn spent_outputs_of(
+    tx: &BitcoinTransaction,
+    spenders: &[BitcoinTransaction],
+) -> Vec<(u32, Txid)> {
+    let txid = tx.txid();
+    let mut spent = Vec::new();
+    for spender in spenders {
+        for input in &spender.inputs {
+            let prev = &input.previous_output;
+            if prev.txid == txid && (prev.vout as usize) < tx.outputs.len() {
+                spent.push((prev.vout, spender.txid()));
+            }
+        }
+    }
+    spent
+}
+
+#[cfg(feature = "hashes")]
+fn sha256d(data: &[u8]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+#[cfg(feature = "hashes")]
+fn sha256_concat(parts: impl IntoIterator<Item = Vec<u8>>) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    let mut engine = Sha256::new();
+    for part in parts {
+        engine.update(part);
+    }
+    engine.finalize().into()
+}
+
+#[cfg(feature = "hashes")]
+fn hash160(data: &[u8]) -> [u8; 20] {
+    use ripemd::Ripemd160;
+    use sha2::{Digest, Sha256};
+    Ripemd160::digest(Sha256::digest(data)).into()
+}
+
+#[cfg(feature = "hashes")]
+fn base58check_encode(prefix: u8, payload: &[u8]) -> String {
+    const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let mut data = vec![prefix];
+    data.extend_from_slice(payload);
+    let checksum = sha256d(&data);
+    data.extend_from_slice(&checksum[..4]);
+
+    // Little-endian base-58 digits, built by repeated multiply-and-add.
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &data {
+        let mut carry = u32::from(byte);
+        for digit in &mut digits {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = data.iter().take_while(|&&byte| byte == 0).count();
+    "1".repeat(zeros)
+        + &digits
+            .iter()
+            .rev()
+            .map(|&digit| ALPHABET[usize::from(digit)] as char)
+            .collect::<String>()
+}
+
+/// BIP-173 bech32 for witness version 0, BIP-350 bech32m otherwise.
+#[cfg(feature = "hashes")]
+fn segwit_encode(hrp: &str, version: u8, program: &[u8]) -> String {
+    const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    const GENERATOR: [u32; 5] = [
+        0x3B6A_57B2,
+        0x2650_8E6D,
+        0x1EA1_19FA,
+        0x3D42_33DD,
+        0x2A14_62B3,
+    ];
+
+    let mut data = vec![version];
+    let (mut acc, mut bits) = (0u32, 0);
+    for &byte in program {
+        acc = ((acc << 8) | u32::from(byte)) & 0xFFF;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            data.push(((acc >> bits) & 0x1F) as u8);
+        }
+    }
+    if bits > 0 {
+        data.push(((acc << (5 - bits)) & 0x1F) as u8);
+    }
+
+    let mut values: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|c| c & 0x1F));
+    values.extend_from_slice(&data);
+    values.extend_from_slice(&[0; 6]);
+    let mut checksum = 1u32;
+    for &value in &values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x01FF_FFFF) << 5) ^ u32::from(value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum ^= if version == 0 { 1 } else { 0x2BC8_30A3 };
+
+    let mut address = format!("{}1", hrp);
+    address.extend(data.iter().map(|&d| CHARSET[usize::from(d)] as char));
+    address.extend((0..6).map(|i| CHARSET[((checksum >> (5 * (5 - i))) & 0x1F) as usize] as char));
+    address
+}
+
+#[cfg(feature = "hashes")]
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut engine = Sha256::new();
+    engine.update(tag_hash);
+    engine.update(tag_hash);
+    engine.update(data);
+    engine.finalize().into()
+}
+
+pub type ControlBlock = (u8, [u8; 32], Vec<[u8; 32]>);
+
+pub fn parse_control_block(cb: &[u8]) -> Result<ControlBlock, BitcoinError> {
+    ensure_len(cb, 33)?;
+    let path_len = cb.len() - 33;
+    if !path_len.is_multiple_of(32) || path_len / 32 > 128 {
+        return Err(BitcoinError::InvalidFormat);
+    }
+    let mut internal_key = [0u8; 32];
+    internal_key.copy_from_slice(&cb[1..33]);
+    let path = cb[33..]
+        .chunks_exact(32)
+        .map(|chunk| {
+            let mut node = [0u8; 32];
+            node.copy_from_slice(chunk);
+            node
+        })
+        .collect();
+    Ok((cb[0], internal_key, path))
+}
+
+/// Structural checks before any signature verification. P2WPKH needs a
+/// signature and a compressed key hashing to the program; P2WSH needs a final
+/// witness script hashing to the program; P2TR needs a 64- or 65-byte
+/// signature or, for a script path, a script and a well-formed control block.
+/// Other witness versions accept any witness, and non-witness outputs none.
+#[cfg(feature = "hashes")]
+pub fn verify_witness_shape(
+    witness: &Witness,
+    prevout_script: &Script,
+) -> Result<(), BitcoinError> {
+    use sha2::{Digest, Sha256};
+
+    let shape_ok = match (witness_program(prevout_script), witness.0.as_slice()) {
+        (None, items) => items.is_empty(),
+        (Some((0, program)), [sig, pubkey]) if program.len() == 20 => {
+            is_signature_push(sig) && pubkey.len() == 33 && hash160(pubkey) == program
+        }
+        (Some((0, program)), [.., script]) if program.len() == 32 => {
+            script.len() <= MAX_SCRIPT_SIZE && Sha256::digest(script).as_slice() == program
+        }
+        (Some((0, _)), _) => false,
+        (Some((1, program)), _) if program.len() == 32 => match witness.without_annex() {
+            [sig] => sig.len() == 64 || sig.len() == 65,
+            [_, .., control_block] => parse_control_block(control_block).is_ok(),
+            [] => false,
+        },
+        (Some(_), _) => true,
+    };
+    if shape_ok {
+        Ok(())
+    } else {
+        Err(BitcoinError::InvalidFormat)
+    }
+}
+
+#[cfg(feature = "hashes")]
+fn tap_branch_hash(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut data = Vec::with_capacity(64);
+    data.extend_from_slice(first);
+    data.extend_from_slice(second);
+    tagged_hash("TapBranch", &data)
+}
+
+/// Pairs nodes level by level, carrying an odd node up unchanged; all zero for no leaves.
+#[cfg(feature = "hashes")]
+pub fn taproot_merkle_root(leaves: &[(u8, Script)]) -> [u8; 32] {
+    let mut level: Vec<[u8; 32]> = leaves
+        .iter()
+        .map(|(version, script)| script.tapleaf_hash(*version))
+        .collect();
+    if level.is_empty() {
+        return [0u8; 32];
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                if let [a, b] = pair {
+                    tap_branch_hash(a, b)
+                } else {
+                    pair[0]
+                }
+            })
+            .collect();
+    }
+    level[0]
+}
+
+#[cfg(feature = "secp256k1")]
+pub fn taproot_tweak_pubkey(
+    internal_key: &[u8; 32],
+    merkle_root: Option<[u8; 32]>,
+) -> Result<([u8; 32], bool), BitcoinError> {
+    use secp256k1::{Parity, Scalar, Secp256k1, XOnlyPublicKey};
+
+    let mut data = internal_key.to_vec();
+    if let Some(root) = merkle_root {
+        data.extend_from_slice(&root);
+    }
+    let tweak = Scalar::from_be_bytes(tagged_hash("TapTweak", &data))
+        .map_err(|_| BitcoinError::InvalidFormat)?;
+    let internal =
+        XOnlyPublicKey::from_slice(internal_key).map_err(|_| BitcoinError::InvalidFormat)?;
+    let (tweaked, parity) = internal
+        .add_tweak(&Secp256k1::verification_only(), &tweak)
+        .map_err(|_| BitcoinError::InvalidFormat)?;
+    Ok((tweaked.serialize(), parity == Parity::Odd))
+}
+
+#[cfg(feature = "secp256k1")]
+pub fn verify_taproot_script_path(
+    output_key: &[u8; 32],
+    control_block: &[u8],
+    script: &Script,
+) -> Result<bool, BitcoinError> {
+    let (leaf_byte, internal_key, path) = parse_control_block(control_block)?;
+    let mut node = script.tapleaf_hash(leaf_byte & 0xFE);
+    for sibling in &path {
+        node = tap_branch_hash(&node, sibling);
+    }
+    let (tweaked, odd) = taproot_tweak_pubkey(&internal_key, Some(node))?;
+    Ok(tweaked == *output_key && odd == (leaf_byte & 1 == 1))
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub prev_block_hash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    pub fn new(
+        version: u32,
+        prev_block_hash: [u8; 32],
+        merkle_root: [u8; 32],
+        time: u32,
+        bits: u32,
+        nonce: u32,
+    ) -> Self {
+        BlockHeader {
+            version,
+            prev_block_hash,
+            merkle_root,
+            time,
+            bits,
+            nonce,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = Vec::with_capacity(80);
+        v.extend_from_slice(&self.version.to_le_bytes());
+        v.extend_from_slice(&self.prev_block_hash);
+        v.extend_from_slice(&self.merkle_root);
+        v.extend_from_slice(&self.time.to_le_bytes());
+        v.extend_from_slice(&self.bits.to_le_bytes());
+        v.extend_from_slice(&self.nonce.to_le_bytes());
+        v
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_len(bytes, 80)?;
+        let read_u32 = |at: usize| {
+            let mut arr = [0u8; 4];
+            arr.copy_from_slice(&bytes[at..at + 4]);
+            u32::from_le_bytes(arr)
+        };
+        let mut prev_block_hash = [0u8; 32];
+        prev_block_hash.copy_from_slice(&bytes[4..36]);
+        let mut merkle_root = [0u8; 32];
+        merkle_root.copy_from_slice(&bytes[36..68]);
+        Ok((
+            BlockHeader::new(
+                read_u32(0),
+                prev_block_hash,
+                merkle_root,
+                read_u32(68),
+                read_u32(72),
+                read_u32(76),
+            ),
+            80,
+        ))
+    }
+
+    /// Big-endian 256-bit target; all zero when `bits` is negative or overflows.
+    pub fn target(&self) -> [u8; 32] {
+        let exponent = (self.bits >> 24) as usize;
+        let mantissa = self.bits & 0x007F_FFFF;
+        let mut target = [0u8; 32];
+        let negative = self.bits & 0x0080_0000 != 0;
+        let overflow = exponent > 34
+            || (mantissa > 0xFF && exponent > 33)
+            || (mantissa > 0xFFFF && exponent > 32);
+        if mantissa == 0 || negative || overflow {
+            return target;
+        }
+        if exponent <= 3 {
+            let value = mantissa >> (8 * (3 - exponent));
+            target[28..].copy_from_slice(&value.to_be_bytes());
+        } else {
+            for (i, b) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+                if *b != 0 {
+                    target[32 + i - exponent] = *b;
+                }
+            }
+        }
+        target
+    }
+
+    /// Expected hashes to find this block, `2^256 / (target + 1)`; saturates at `u128::MAX`.
+    pub fn work(&self) -> u128 {
+        let target = self.target();
+        let mut half = [0u8; 16];
+        half.copy_from_slice(&target[..16]);
+        let hi = u128::from_be_bytes(half);
+        half.copy_from_slice(&target[16..]);
+        let lo = u128::from_be_bytes(half);
+        if hi == 0 && lo == 0 {
+            return 0;
+        }
+        if hi == 0 {
+            return u128::MAX;
+        }
+
+        // 2^256 / (target + 1) == !target / (target + 1) + 1, computed by long division.
+        let (d_lo, carry) = lo.overflowing_add(1);
+        let d_hi = hi + u128::from(carry);
+        let (n_hi, n_lo) = (!hi, !lo);
+        let (mut r_hi, mut r_lo) = (0u128, 0u128);
+        let mut quotient = 0u128;
+        for i in (0..256).rev() {
+            let bit = if i >= 128 {
+                (n_hi >> (i - 128)) & 1
+            } else {
+                (n_lo >> i) & 1
+            };
+            let carry_out = r_hi >> 127 == 1;
+            r_hi = (r_hi << 1) | (r_lo >> 127);
+            r_lo = (r_lo << 1) | bit;
+            quotient <<= 1;
+            if carry_out || (r_hi, r_lo) >= (d_hi, d_lo) {
+                let (new_lo, borrow) = r_lo.overflowing_sub(d_lo);
+                r_hi = r_hi.wrapping_sub(d_hi).wrapping_sub(u128::from(borrow));
+                r_lo = new_lo;
+                quotient |= 1;
+            }
+        }
+        quotient + 1
+    }
+}
+
+#[cfg(feature = "hashes")]
+impl BlockHeader {
+    pub fn block_hash(&self) -> [u8; 32] {
+        sha256d(&self.to_bytes())
+    }
+
+    pub fn meets_target(&self) -> bool {
+        let target = self.target();
+        let mut hash = self.block_hash();
+        hash.reverse();
+        target != [0u8; 32] && hash <= target
+    }
+}
+
+#[cfg(feature = "hashes")]
+pub fn verify_block_merkle(header: &BlockHeader, txs: &[BitcoinTransaction]) -> bool {
+    let mut level: Vec<[u8; 32]> = txs.iter().map(|tx| tx.txid().0).collect();
+    if level.is_empty() {
+        return false;
+    }
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1]);
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| sha256d(&[pair[0], pair[1]].concat()))
+            .collect();
+    }
+    level[0] == header.merkle_root
+}
+
+pub fn parse_block_transactions(
+    bytes: &[u8],
+) -> Result<(Vec<BitcoinTransaction>, usize), BitcoinError> {
+    let (count, mut offset) = CompactSize::from_bytes(bytes)?;
+    let mut txs = Vec::new();
+    for _ in 0..count.value {
+        let (tx, consumed) = BitcoinTransaction::from_bytes(&bytes[offset..])?;
+        txs.push(tx);
+        offset += consumed;
+    }
+    Ok((txs, offset))
+}
+
+pub fn parse_block(bytes: &[u8]) -> Result<(BlockHeader, Vec<BitcoinTransaction>), BitcoinError> {
+    let (header, consumed1) = BlockHeader::from_bytes(bytes)?;
+    let (txs, consumed2) = parse_block_transactions(&bytes[consumed1..])?;
+    if consumed1 + consumed2 != bytes.len() {
+        return Err(BitcoinError::InvalidFormat);
+    }
+    Ok((header, txs))
+}
+
+pub struct BlockFileReader<R: Read> {
+    reader: R,
+    magic: [u8; 4],
+    done: bool,
+}
+
+impl<R: Read> BlockFileReader<R> {
+    pub fn new(reader: R, magic: [u8; 4]) -> Self {
+        BlockFileReader {
+            reader,
+            magic,
+            done: false,
+        }
+    }
+
+    fn read_block(&mut self) -> Result<Option<Vec<u8>>, BitcoinError> {
+        let mut record = [0u8; 8];
+        let mut filled = 0;
+        while filled < record.len() {
+            match self.reader.read(&mut record[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(BitcoinError::Io(e.kind())),
+            }
+        }
+        // Block files are preallocated, so a run of zeros marks the end of the data.
+        if filled == 0 || (filled >= 4 && record[..4] == [0u8; 4]) {
+            return Ok(None);
+        }
+        ensure_len(&record[..filled], 8)?;
+        if record[..4] != self.magic {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let mut size_bytes = [0u8; 4];
+        size_bytes.copy_from_slice(&record[4..8]);
+        let size = u32::from_le_bytes(size_bytes) as usize;
+        if size > MAX_BLOCK_SERIALIZED_SIZE {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let mut block = vec![0u8; size];
+        self.reader
+            .read_exact(&mut block)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => BitcoinError::InsufficientBytes,
+                kind => BitcoinError::Io(kind),
+            })?;
+        Ok(Some(block))
+    }
+}
+
+impl<R: Read> Iterator for BlockFileReader<R> {
+    type Item = Result<(BlockHeader, Vec<BitcoinTransaction>), BitcoinError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self
+            .read_block()
+            .and_then(|block| block.map(|b| parse_block(&b)).transpose());
+        match result {
+            Ok(Some(block)) => Some(Ok(block)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PsbtInput {
+    pub prevout_script: Option<Script>,
+    pub partial_sigs: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PartiallySignedTransaction {
+    pub unsigned_tx: BitcoinTransaction,
+    pub inputs: Vec<PsbtInput>,
+}
+
+impl PartiallySignedTransaction {
+    pub fn new(unsigned_tx: BitcoinTransaction) -> Self {
+        let inputs = vec![PsbtInput::default(); unsigned_tx.inputs.len()];
+        PartiallySignedTransaction {
+            unsigned_tx,
+            inputs,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = self.unsigned_tx.to_bytes();
+        for input in &self.inputs {
+            match &input.prevout_script {
+                Some(script) => {
+                    v.push(1);
+                    v.extend_from_slice(&script.to_bytes());
+                }
+                None => v.push(0),
+            }
+            v.extend_from_slice(&CompactSize::new(input.partial_sigs.len() as u64).to_bytes());
+            for (pubkey, sig) in &input.partial_sigs {
+                write_var_bytes(&mut v, pubkey);
+                write_var_bytes(&mut v, sig);
+            }
+        }
+        v
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let (unsigned_tx, mut offset) = BitcoinTransaction::from_bytes(bytes)?;
+        let mut inputs = Vec::with_capacity(unsigned_tx.inputs.len());
+        for _ in 0..unsigned_tx.inputs.len() {
+            ensure_len(&bytes[offset..], 1)?;
+            let prevout_script = match bytes[offset] {
+                0 => {
+                    offset += 1;
+                    None
+                }
+                1 => {
+                    let (script, consumed) = Script::from_bytes(&bytes[offset + 1..])?;
+                    offset += 1 + consumed;
+                    Some(script)
+                }
+                _ => return Err(BitcoinError::InvalidFormat),
+            };
+            let (count, consumed) = CompactSize::from_bytes(&bytes[offset..])?;
+            offset += consumed;
+            let mut partial_sigs = Vec::new();
+            for _ in 0..count.value {
+                let (pubkey, consumed) = read_var_bytes(&bytes[offset..])?;
+                offset += consumed;
+                let (sig, consumed) = read_var_bytes(&bytes[offset..])?;
+                offset += consumed;
+                partial_sigs.push((pubkey, sig));
+            }
+            inputs.push(PsbtInput {
+                prevout_script,
+                partial_sigs,
+            });
+        }
+        Ok((
+            PartiallySignedTransaction {
+                unsigned_tx,
+                inputs,
+            },
+            offset,
+        ))
+    }
+
+    /// Merges another signer's view of the same transaction into this one.
+    /// Disagreeing prevout scripts, or two different signatures for the same
+    /// pubkey, are rejected with `InvalidFormat` before anything is merged.
+    pub fn combine(&mut self, other: &PartiallySignedTransaction) -> Result<(), BitcoinError> {
+        if self.unsigned_tx != other.unsigned_tx || self.inputs.len() != other.inputs.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        for (input, theirs) in self.inputs.iter().zip(&other.inputs) {
+            if let (Some(ours), Some(their_script)) =
+                (&input.prevout_script, &theirs.prevout_script)
+                && ours != their_script
+            {
+                return Err(BitcoinError::InvalidFormat);
+            }
+            let conflicting_sig = theirs.partial_sigs.iter().any(|(pubkey, sig)| {
+                input
+                    .partial_sigs
+                    .iter()
+                    .any(|(pk, existing)| pk == pubkey && existing != sig)
+            });
+            if conflicting_sig {
+                return Err(BitcoinError::InvalidFormat);
+            }
+        }
+        for (input, theirs) in self.inputs.iter_mut().zip(&other.inputs) {
+            if input.prevout_script.is_none() {
+                input.prevout_script = theirs.prevout_script.clone();
+            }
+            for (pubkey, sig) in &theirs.partial_sigs {
+                if !input.partial_sigs.iter().any(|(pk, _)| pk == pubkey) {
+                    input.partial_sigs.push((pubkey.clone(), sig.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the final scriptSigs from the collected partial signatures.
+    /// P2PKH inputs use the signature whose pubkey hashes to the prevout, so
+    /// they need the `hashes` feature; without it they are `InvalidFormat`.
+    pub fn finalize(&self) -> Result<BitcoinTransaction, BitcoinError> {
+        if self.inputs.len() != self.unsigned_tx.inputs.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let mut tx = self.unsigned_tx.clone();
+        for (tx_input, input) in tx.inputs.iter_mut().zip(&self.inputs) {
+            let prevout = input
+                .prevout_script
+                .as_ref()
+                .ok_or(BitcoinError::Incomplete)?;
+            let mut script_sig = Vec::new();
+            match prevout.script_type() {
+                ScriptType::P2pk => {
+                    let key = &prevout[1..prevout.len() - 1];
+                    let (_, sig) = input
+                        .partial_sigs
+                        .iter()
+                        .find(|(pk, _)| pk == key)
+                        .ok_or(BitcoinError::Incomplete)?;
+                    push_data(&mut script_sig, sig);
+                }
+                #[cfg(feature = "hashes")]
+                ScriptType::P2pkh => {
+                    let (pubkey, sig) = input
+                        .partial_sigs
+                        .iter()
+                        .find(|(pk, _)| hash160(pk)[..] == prevout[3..23])
+                        .ok_or(BitcoinError::Incomplete)?;
+                    push_data(&mut script_sig, sig);
+                    push_data(&mut script_sig, pubkey);
+                }
+                ScriptType::Multisig => {
+                    let (m, _) = prevout
+                        .multisig_threshold()
+                        .ok_or(BitcoinError::InvalidFormat)?;
+                    let sigs: Vec<&Vec<u8>> = prevout
+                        .instructions()
+                        .filter_map(|ins| match ins {
+                            Ok(Instruction::Push(key)) => input
+                                .partial_sigs
+                                .iter()
+                                .find(|(pk, _)| pk == key)
+                                .map(|(_, sig)| sig),
+                            _ => None,
+                        })
+                        .take(usize::from(m))
+                        .collect();
+                    if sigs.len() < usize::from(m) {
+                        return Err(BitcoinError::Incomplete);
+                    }
+                    script_sig.push(0x00);
+                    for sig in sigs {
+                        push_data(&mut script_sig, sig);
+                    }
+                }
+                _ => return Err(BitcoinError::InvalidFormat),
+            }
+            tx_input.script_sig = Script::new(script_sig);
+        }
+        Ok(tx)
+    }
+}
+
+fn write_var_bytes(v: &mut Vec<u8>, data: &[u8]) {
+    v.extend_from_slice(&CompactSize::new(data.len() as u64).to_bytes());
+    v.extend_from_slice(data);
 }
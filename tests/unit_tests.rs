@@ -10,6 +10,41 @@ mod tests {
         txid
     }
 
+    #[cfg(feature = "hashes")]
+    fn genesis_coinbase() -> BitcoinTransaction {
+        let bytes = hex::decode(concat!(
+            "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff",
+            "4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72",
+            "206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff",
+            "0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f",
+            "61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000",
+        ))
+        .unwrap();
+        BitcoinTransaction::from_bytes(&bytes).unwrap().0
+    }
+
+    fn multisig_script(m: u8, keys: &[u8]) -> Script {
+        let mut bytes = vec![0x50 + m];
+        for &key in keys {
+            bytes.push(33);
+            bytes.extend_from_slice(&[key; 33]);
+        }
+        bytes.extend_from_slice(&[0x50 + keys.len() as u8, 0xAE]);
+        Script::new(bytes)
+    }
+
+    fn p2pkh_script() -> Script {
+        Script::new(hex::decode("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").unwrap())
+    }
+
+    fn input_with_script(len: usize) -> TransactionInput {
+        TransactionInput::new(
+            OutPoint::new(dummy_txid(1), 0),
+            Script::new(vec![0x00; len]),
+            0xFFFFFFFF,
+        )
+    }
+
     #[test]
     fn test_compact_size_serialization() {
         let tests = vec![
@@ -110,4 +145,2257 @@ mod tests {
         assert!(output.contains("Lock Time: 0"));
         assert!(output.contains("Previous Output Vout: 7"));
     }
+
+    #[test]
+    fn test_parse_control_block() {
+        let internal_key =
+            hex::decode("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27")
+                .unwrap();
+        let mut cb = vec![0xC1];
+        cb.extend_from_slice(&internal_key);
+        cb.extend_from_slice(&[0x11; 32]);
+        cb.extend_from_slice(&[0x22; 32]);
+
+        let (leaf_byte, key, path) = parse_control_block(&cb).unwrap();
+        assert_eq!(leaf_byte, 0xC1);
+        assert_eq!(key.to_vec(), internal_key);
+        assert_eq!(path, vec![[0x11; 32], [0x22; 32]]);
+
+        let (_, _, path) = parse_control_block(&cb[..33]).unwrap();
+        assert!(path.is_empty());
+
+        assert_eq!(
+            parse_control_block(&cb[..32]),
+            Err(BitcoinError::InsufficientBytes)
+        );
+        assert_eq!(
+            parse_control_block(&cb[..40]),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        let script = vec![0x51];
+        let spend = Witness::new(vec![vec![0x01; 64], script.clone(), cb.clone()]);
+        assert_eq!(spend.taproot_control_block(), Some(cb.as_slice()));
+        let mut with_annex = spend.clone();
+        with_annex.0.push(vec![0x50, 0x00]);
+        assert_eq!(with_annex.taproot_control_block(), Some(cb.as_slice()));
+        let key_path = Witness::new(vec![vec![0x01; 64]]);
+        assert_eq!(key_path.taproot_control_block(), None);
+        let key_path_with_annex = Witness::new(vec![vec![0x01; 64], vec![0x50]]);
+        assert_eq!(key_path_with_annex.taproot_control_block(), None);
+    }
+
+    #[test]
+    fn test_witness_merge() {
+        let script = multisig_script(2, &[0x02, 0x03]).bytes;
+        let mut ours = Witness::new(vec![vec![], vec![0x30; 71], vec![], script.clone()]);
+        let theirs = Witness::new(vec![vec![], vec![], vec![0x31; 72], script.clone()]);
+        ours.merge(&theirs).unwrap();
+        assert_eq!(
+            ours,
+            Witness::new(vec![vec![], vec![0x30; 71], vec![0x31; 72], script.clone()])
+        );
+
+        let conflicting = Witness::new(vec![vec![], vec![0x32; 71], vec![], script.clone()]);
+        let before = ours.clone();
+        assert_eq!(ours.merge(&conflicting), Err(BitcoinError::InvalidFormat));
+        assert_eq!(ours, before);
+
+        let shorter = Witness::new(vec![vec![], vec![0x30; 71]]);
+        assert_eq!(ours.merge(&shorter), Err(BitcoinError::InvalidFormat));
+    }
+
+    #[test]
+    fn test_witness_is_taproot_keypath() {
+        assert!(Witness::new(vec![vec![0x01; 64]]).is_taproot_keypath());
+        assert!(Witness::new(vec![vec![0x01; 65]]).is_taproot_keypath());
+        assert!(Witness::new(vec![vec![0x01; 64], vec![0x50, 0x01]]).is_taproot_keypath());
+        assert!(
+            !Witness::new(vec![vec![0x01; 64], vec![0x51], vec![0xC0; 33]]).is_taproot_keypath()
+        );
+        assert!(!Witness::new(vec![vec![0x30; 71], vec![0x02; 33]]).is_taproot_keypath());
+        assert!(!Witness::new(vec![vec![0x01; 63]]).is_taproot_keypath());
+        assert!(!Witness::default().is_taproot_keypath());
+    }
+
+    #[test]
+    fn test_witness_to_hex_items() {
+        let witness = Witness::new(vec![vec![0x30, 0x44, 0x01], vec![0x02; 3]]);
+        assert_eq!(witness.to_hex_items(), vec!["304401", "020202"]);
+        assert!(Witness::default().to_hex_items().is_empty());
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_low_s_signatures() {
+        let low_s = hex::decode("300602010102010101").unwrap();
+        let high_s = hex::decode(
+            "3026020101022100fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd036414001",
+        )
+        .unwrap();
+        let pubkey = [0x02; 33];
+
+        let script_with = |sig: &[u8]| {
+            let mut bytes = vec![sig.len() as u8];
+            bytes.extend_from_slice(sig);
+            bytes.push(pubkey.len() as u8);
+            bytes.extend_from_slice(&pubkey);
+            Script::new(bytes)
+        };
+
+        let low = script_with(&low_s);
+        assert!(low.has_low_s_signatures());
+
+        let mut high = script_with(&high_s);
+        assert!(!high.has_low_s_signatures());
+        high.normalize_low_s();
+        assert!(high.has_low_s_signatures());
+        assert_eq!(high, low);
+
+        assert!(!Script::new(vec![0x4C]).has_low_s_signatures());
+    }
+
+    #[test]
+    fn test_parsers_insufficient_bytes_boundary() {
+        fn check<T: std::fmt::Debug>(
+            bytes: &[u8],
+            parse: impl Fn(&[u8]) -> Result<(T, usize), BitcoinError>,
+        ) {
+            assert!(parse(bytes).is_ok());
+            assert_eq!(
+                parse(&bytes[..bytes.len() - 1]).unwrap_err(),
+                BitcoinError::InsufficientBytes
+            );
+        }
+
+        check(&CompactSize::new(0xFC).to_bytes(), CompactSize::from_bytes);
+        check(
+            &CompactSize::new(0xFFFF).to_bytes(),
+            CompactSize::from_bytes,
+        );
+        check(
+            &CompactSize::new(0xFFFF_FFFF).to_bytes(),
+            CompactSize::from_bytes,
+        );
+        check(
+            &CompactSize::new(u64::MAX).to_bytes(),
+            CompactSize::from_bytes,
+        );
+
+        let outpoint = OutPoint::new(dummy_txid(1), 0);
+        check(&outpoint.to_bytes(), OutPoint::from_bytes);
+
+        let script = Script::new(vec![0x51, 0x52]);
+        check(&script.to_bytes(), Script::from_bytes);
+
+        let input = TransactionInput::new(outpoint, script, 0xFFFFFFFF);
+        check(&input.to_bytes(), TransactionInput::from_bytes);
+
+        let output = TransactionOutput::new(50_000, Script::new(vec![0x51]));
+        check(&output.to_bytes(), TransactionOutput::from_bytes);
+
+        let mut tx = BitcoinTransaction::new(2, vec![input], 0);
+        tx.outputs.push(output);
+        check(&tx.to_bytes(), BitcoinTransaction::from_bytes);
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&[0x02, 0x00, 0x00]),
+            Err(BitcoinError::InsufficientBytes)
+        );
+    }
+
+    #[test]
+    fn test_script_to_descriptor() {
+        let hash = "751e76e8199196d454941c45d1b3a323f1433bd6";
+        let p2wpkh = Script::new(hex::decode(format!("0014{}", hash)).unwrap());
+        assert_eq!(p2wpkh.script_type(), ScriptType::P2wpkh);
+        assert_eq!(p2wpkh.to_descriptor(), Some(format!("wpkh({})", hash)));
+
+        let p2pkh = Script::new(hex::decode(format!("76a914{}88ac", hash)).unwrap());
+        assert_eq!(p2pkh.to_descriptor(), Some(format!("pkh({})", hash)));
+
+        let key = "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c";
+        let p2tr = Script::new(hex::decode(format!("5120{}", key)).unwrap());
+        assert_eq!(p2tr.to_descriptor(), Some(format!("rawtr({})", key)));
+
+        let nonstandard = Script::new(vec![0x51, 0x87]);
+        assert_eq!(nonstandard.script_type(), ScriptType::NonStandard);
+        assert_eq!(nonstandard.to_descriptor(), None);
+    }
+
+    #[test]
+    fn test_exceeds_max_weight() {
+        let normal = BitcoinTransaction::new(2, vec![input_with_script(107)], 0);
+        assert_eq!(normal.weight(), normal.to_bytes().len() * 4);
+        assert!(!normal.exceeds_max_weight());
+
+        let oversized = BitcoinTransaction::new(2, vec![input_with_script(100_000); 10], 0);
+        assert!(oversized.weight() > 4_000_000);
+        assert!(oversized.exceeds_max_weight());
+    }
+
+    #[test]
+    fn test_fee_rate_sat_per_kwu() {
+        let input = input_with_script(0);
+        let tx = BitcoinTransaction::new(2, vec![input], 0);
+        // 4 (version) + 1 (count) + 41 (input) + 1 (count) + 4 (lock_time) = 51 bytes = 204 WU
+        assert_eq!(tx.weight(), 204);
+        assert_eq!(tx.fee_rate_sat_per_kwu(1020), 5000.0);
+    }
+
+    #[test]
+    fn test_is_timelocked() {
+        let input = |sequence: u32| TransactionInput {
+            sequence,
+            ..input_with_script(0)
+        };
+
+        let all_final = BitcoinTransaction::new(2, vec![input(0xFFFFFFFF); 2], 800_000);
+        assert!(!all_final.is_timelocked());
+
+        let enforced =
+            BitcoinTransaction::new(2, vec![input(0xFFFFFFFF), input(0xFFFFFFFE)], 800_000);
+        assert!(enforced.is_timelocked());
+
+        let no_lock_time = BitcoinTransaction::new(2, vec![input(0xFFFFFFFE)], 0);
+        assert!(!no_lock_time.is_timelocked());
+    }
+
+    #[test]
+    fn test_script_execution_cost() {
+        let p2pkh = p2pkh_script();
+        let multisig = multisig_script(2, &[0x02, 0x02, 0x02]);
+        let push_only = Script::new(vec![0x01, 0xFF, 0x51]);
+
+        assert!(push_only.execution_cost() < p2pkh.execution_cost());
+        assert!(p2pkh.execution_cost() < multisig.execution_cost());
+    }
+
+    #[test]
+    fn test_outpoint_list_roundtrip() {
+        let outpoints = vec![
+            OutPoint::new(dummy_txid(1), 0),
+            OutPoint::new(dummy_txid(2), 5),
+            OutPoint::new(dummy_txid(3), 0xFFFFFFFF),
+        ];
+        let bytes = serialize_outpoints(&outpoints);
+        assert_eq!(bytes.len(), 1 + 3 * 36);
+        let (parsed, consumed) = parse_outpoints(&bytes).unwrap();
+        assert_eq!(parsed, outpoints);
+        assert_eq!(consumed, bytes.len());
+    }
+
+    #[test]
+    fn test_is_final_at() {
+        let input = TransactionInput {
+            sequence: 0xFFFFFFFE,
+            ..input_with_script(0)
+        };
+
+        let by_height = BitcoinTransaction::new(2, vec![input.clone()], 800_000);
+        assert!(!by_height.is_final_at(800_000, 1_700_000_000));
+        assert!(by_height.is_final_at(800_001, 0));
+
+        let by_time = BitcoinTransaction::new(2, vec![input.clone()], 1_700_000_000);
+        assert!(!by_time.is_final_at(u32::MAX, 1_700_000_000));
+        assert!(by_time.is_final_at(0, 1_700_000_001));
+
+        let mut all_final = by_time.clone();
+        all_final.inputs[0].sequence = 0xFFFFFFFF;
+        assert!(all_final.is_final_at(0, 0));
+    }
+
+    #[test]
+    fn test_validate_sequence_bip68() {
+        let input = |sequence: u32| TransactionInput {
+            sequence,
+            ..input_with_script(0)
+        };
+
+        let relative_with_reserved = input(0x0001_0000 | 144);
+        assert_eq!(relative_with_reserved.validate_sequence(1), Ok(()));
+        assert_eq!(
+            relative_with_reserved.validate_sequence(2),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        assert_eq!(input(144).validate_sequence(2), Ok(()));
+        assert_eq!(input((1 << 22) | 10).validate_sequence(2), Ok(()));
+        assert_eq!(input(0xFFFFFFFE).validate_sequence(2), Ok(()));
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let input = TransactionInput::new(
+            OutPoint::new(dummy_txid(1), 0),
+            Script::new(vec![0x01, 0x02]),
+            0xFFFFFFFF,
+        );
+        let tx = BitcoinTransaction::new(2, vec![input], 0);
+        let same = tx.clone();
+        let mut other = tx.clone();
+        other.lock_time = 1;
+
+        let json = tx.canonical_json();
+        assert_eq!(json, same.canonical_json());
+        assert_ne!(json, other.canonical_json());
+        assert!(json.starts_with("{\"inputs\":[{\"previous_output\":"));
+        assert!(json.ends_with(",\"lock_time\":0,\"outputs\":[],\"version\":2}"));
+        assert!(json.contains("\"script_sig\":{\"bytes\":[1,2]},\"sequence\":4294967295"));
+
+        let mut paying = tx.clone();
+        paying
+            .outputs
+            .push(TransactionOutput::new(1_000, Script::new(vec![0x51])));
+        assert!(
+            paying
+                .canonical_json()
+                .contains("\"outputs\":[{\"script_pubkey\":{\"bytes\":[81]},\"value\":1000}]")
+        );
+    }
+
+    #[test]
+    fn test_bitcoin_tx_from_bytes_at() {
+        let input = TransactionInput::new(
+            OutPoint::new(dummy_txid(1), 0),
+            Script::new(vec![0x01, 0x02]),
+            0xFFFFFFFF,
+        );
+        let first = BitcoinTransaction::new(1, vec![input.clone()], 0);
+        let second = BitcoinTransaction::new(2, vec![input.clone(), input], 7);
+
+        // A one-byte prefix leaves every multi-byte field unaligned.
+        let mut buf = vec![0xEE];
+        buf.extend_from_slice(&first.to_bytes());
+        buf.extend_from_slice(&second.to_bytes());
+
+        let (parsed, _) = BitcoinTransaction::from_bytes(&buf[1..]).unwrap();
+        assert_eq!(parsed, first);
+
+        let (parsed_first, end) = BitcoinTransaction::from_bytes_at(&buf, 1).unwrap();
+        assert_eq!(parsed_first, first);
+        let (parsed_second, end) = BitcoinTransaction::from_bytes_at(&buf, end).unwrap();
+        assert_eq!(parsed_second, second);
+        assert_eq!(end, buf.len());
+
+        assert_eq!(
+            BitcoinTransaction::from_bytes_at(&buf, buf.len() + 1),
+            Err(BitcoinError::InsufficientBytes)
+        );
+    }
+
+    #[test]
+    fn test_billable_size() {
+        let input = |script: Vec<u8>| {
+            TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::new(script), 0)
+        };
+
+        let normal = BitcoinTransaction::new(2, vec![input(vec![0x01, 0x02])], 0);
+        assert_eq!(normal.sigop_count(), 0);
+        assert_eq!(normal.billable_size(), normal.vsize());
+
+        let sigop_heavy = BitcoinTransaction::new(2, vec![input(vec![0xAC; 100])], 0);
+        assert_eq!(sigop_heavy.sigop_count(), 100);
+        assert!(sigop_heavy.vsize() < 100 * 20);
+        assert_eq!(sigop_heavy.billable_size(), 100 * 20);
+
+        let mut paying = normal.clone();
+        paying
+            .outputs
+            .push(TransactionOutput::new(1_000, p2pkh_script()));
+        paying.outputs.push(TransactionOutput::new(
+            1_000,
+            multisig_script(1, &[0x02, 0x03]),
+        ));
+        assert_eq!(paying.sigop_count(), 1 + 20);
+    }
+
+    #[test]
+    fn test_referenced_pubkeys_p2pkh() {
+        let sig = [0x30; 71];
+        let pubkey = [0x03; 33];
+        let mut script_sig = vec![sig.len() as u8];
+        script_sig.extend_from_slice(&sig);
+        script_sig.push(pubkey.len() as u8);
+        script_sig.extend_from_slice(&pubkey);
+
+        let input = TransactionInput::new(
+            OutPoint::new(dummy_txid(1), 0),
+            Script::new(script_sig),
+            0xFFFFFFFF,
+        );
+        let tx = BitcoinTransaction::new(1, vec![input], 0);
+        let prevout = p2pkh_script();
+
+        assert_eq!(tx.referenced_pubkeys(&[prevout]), vec![pubkey.to_vec()]);
+    }
+
+    #[test]
+    fn test_has_valid_der_signatures() {
+        let push = |sig: &str| {
+            let sig = hex::decode(sig).unwrap();
+            let mut bytes = vec![sig.len() as u8];
+            bytes.extend_from_slice(&sig);
+            Script::new(bytes)
+        };
+
+        let valid = push("300602010102010101");
+        assert!(valid.has_valid_der_signatures());
+
+        let padded_r = push("30070202000102010101");
+        assert!(!padded_r.has_valid_der_signatures());
+
+        let negative_s = push("300602010102018101");
+        assert!(!negative_s.has_valid_der_signatures());
+
+        let wrong_length = push("300702010102010101");
+        assert!(!wrong_length.has_valid_der_signatures());
+
+        assert!(Script::new(vec![0x51]).has_valid_der_signatures());
+        assert!(!Script::new(vec![0x4C]).has_valid_der_signatures());
+        assert!(!Script::new(vec![0x4E, 1, 2, 3]).has_valid_der_signatures());
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let header = BlockHeader::new(
+            4,
+            dummy_txid(1),
+            dummy_txid(2),
+            1_700_000_000,
+            0x1703_6E3D,
+            42,
+        );
+        let coinbase = BitcoinTransaction::new(
+            1,
+            vec![TransactionInput::new(
+                OutPoint::new([0u8; 32], 0xFFFFFFFF),
+                Script::new(vec![0x03, 0x01, 0x02, 0x03]),
+                0xFFFFFFFF,
+            )],
+            0,
+        );
+        let spend = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(3), 1),
+                Script::new(vec![0x01, 0x02]),
+                0xFFFFFFFE,
+            )],
+            0,
+        );
+
+        let mut block = header.to_bytes();
+        assert_eq!(block.len(), 80);
+        block.extend_from_slice(&CompactSize::new(2).to_bytes());
+        block.extend_from_slice(&coinbase.to_bytes());
+        block.extend_from_slice(&spend.to_bytes());
+
+        let (parsed_header, txs) = parse_block(&block).unwrap();
+        assert_eq!(parsed_header, header);
+        assert_eq!(txs, vec![coinbase, spend]);
+
+        block.push(0x00);
+        assert_eq!(parse_block(&block), Err(BitcoinError::InvalidFormat));
+    }
+
+    #[test]
+    fn test_block_header_target() {
+        let header = |bits: u32| BlockHeader::new(1, [0u8; 32], [0u8; 32], 0, bits, 0);
+
+        let genesis = header(0x1D00_FFFF).target();
+        assert_eq!(
+            hex::encode(genesis),
+            "00000000ffff0000000000000000000000000000000000000000000000000000"
+        );
+
+        let block_125552 = header(0x1A44_B9F2).target();
+        assert_eq!(
+            hex::encode(block_125552),
+            "00000000000044b9f20000000000000000000000000000000000000000000000"
+        );
+
+        assert_eq!(header(0x0312_3456).target()[29..], [0x12, 0x34, 0x56]);
+        assert_eq!(header(0x0180_0000).target(), [0u8; 32]);
+        assert_eq!(header(0x2301_0000).target(), [0u8; 32]);
+    }
+
+    #[test]
+    fn test_block_header_work() {
+        let header = |bits: u32| BlockHeader::new(1, [0u8; 32], [0u8; 32], 0, bits, 0);
+
+        assert_eq!(header(0x1D00_FFFF).work(), 0x1_0001_0001);
+        assert_eq!(header(0x1A44_B9F2).work(), 1_048_471_149_936_089);
+        assert_eq!(header(0x1703_6E3D).work(), 352_392_985_398_966_937_510_481);
+        assert_eq!(header(0x0180_0000).work(), 0);
+        assert_eq!(header(0x0101_0000).work(), u128::MAX);
+    }
+
+    #[test]
+    fn test_block_file_reader() {
+        let magic = [0xF9, 0xBE, 0xB4, 0xD9];
+        let block = |nonce: u32, tx_version: u32| {
+            let header = BlockHeader::new(1, [0u8; 32], dummy_txid(9), 0, 0x1D00_FFFF, nonce);
+            let tx = BitcoinTransaction::new(
+                tx_version,
+                vec![TransactionInput::new(
+                    OutPoint::new(dummy_txid(1), 0),
+                    Script::new(vec![0x01, 0x02]),
+                    0xFFFFFFFF,
+                )],
+                0,
+            );
+            let mut bytes = header.to_bytes();
+            bytes.extend_from_slice(&CompactSize::new(1).to_bytes());
+            bytes.extend_from_slice(&tx.to_bytes());
+            (header, tx, bytes)
+        };
+        let (header1, tx1, bytes1) = block(1, 1);
+        let (header2, tx2, bytes2) = block(2, 2);
+
+        let mut file = Vec::new();
+        for bytes in [&bytes1, &bytes2] {
+            file.extend_from_slice(&magic);
+            file.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            file.extend_from_slice(bytes);
+        }
+        file.extend_from_slice(&[0u8; 16]);
+
+        let blocks: Vec<_> = BlockFileReader::new(&file[..], magic).collect();
+        assert_eq!(
+            blocks,
+            vec![Ok((header1, vec![tx1])), Ok((header2, vec![tx2]))]
+        );
+
+        let mut reader = BlockFileReader::new(&file[..], [0x0B, 0x11, 0x09, 0x07]);
+        assert_eq!(reader.next(), Some(Err(BitcoinError::InvalidFormat)));
+        assert_eq!(reader.next(), None);
+
+        let truncated = &file[..bytes1.len()];
+        let mut reader = BlockFileReader::new(truncated, magic);
+        assert_eq!(reader.next(), Some(Err(BitcoinError::InsufficientBytes)));
+
+        let mut huge_count = bytes1[..80].to_vec();
+        huge_count.extend_from_slice(&CompactSize::new(1).to_bytes());
+        huge_count.extend_from_slice(&1u32.to_le_bytes());
+        huge_count.extend_from_slice(&CompactSize::new(u64::MAX).to_bytes());
+        let mut file = magic.to_vec();
+        file.extend_from_slice(&(huge_count.len() as u32).to_le_bytes());
+        file.extend_from_slice(&huge_count);
+        let mut reader = BlockFileReader::new(&file[..], magic);
+        assert_eq!(reader.next(), Some(Err(BitcoinError::InsufficientBytes)));
+    }
+
+    #[test]
+    fn test_multisig_threshold() {
+        let multisig = multisig_script(2, &[0x02, 0x03, 0x02]);
+        assert_eq!(multisig.multisig_threshold(), Some((2, 3)));
+        assert_eq!(multisig.script_type(), ScriptType::Multisig);
+
+        let p2pkh = p2pkh_script();
+        assert_eq!(p2pkh.multisig_threshold(), None);
+    }
+
+    #[test]
+    fn test_taproot_output_key() {
+        let key = hex::decode("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343")
+            .unwrap();
+        let mut bytes = vec![0x51, 0x20];
+        bytes.extend_from_slice(&key);
+        let p2tr = Script::new(bytes);
+        assert_eq!(
+            p2tr.taproot_output_key().map(|k| k.to_vec()),
+            Some(key.clone())
+        );
+
+        let mut p2wsh = vec![0x00, 0x20];
+        p2wsh.extend_from_slice(&key);
+        assert_eq!(Script::new(p2wsh).taproot_output_key(), None);
+    }
+
+    #[test]
+    fn test_package_fee_rate() {
+        let input = input_with_script(58);
+        let parent = BitcoinTransaction::new(2, vec![input.clone()], 0);
+        let child = BitcoinTransaction::new(2, vec![input], 0);
+        assert_eq!(parent.vsize(), 109);
+
+        let rate = package_fee_rate(&[parent.clone(), child.clone()], &[109, 1090]).unwrap();
+        assert_eq!(rate, 5.5);
+        assert!(rate > 109.0 / parent.vsize() as f64);
+
+        assert_eq!(
+            package_fee_rate(&[parent, child], &[1]),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_spend_scriptsig_size() {
+        assert_eq!(Script::spend_scriptsig_size(ScriptType::P2pkh), 107);
+        assert_eq!(Script::spend_scriptsig_size(ScriptType::P2pk), 73);
+        assert_eq!(Script::spend_scriptsig_size(ScriptType::P2sh), 23);
+        assert_eq!(Script::spend_scriptsig_size(ScriptType::P2wpkh), 0);
+        assert_eq!(Script::spend_scriptsig_size(ScriptType::P2tr), 0);
+    }
+
+    #[test]
+    fn test_segwit_scriptcode() {
+        let hash = "1d0f172a0ecb48aee1be1f2687d2963ae33f71a1";
+        let p2wpkh = Script::new(hex::decode(format!("0014{}", hash)).unwrap());
+        let expected = Script::new(hex::decode(format!("76a914{}88ac", hash)).unwrap());
+        assert_eq!(p2wpkh.segwit_scriptcode(), Some(expected));
+
+        let p2wsh = Script::new([vec![0x00, 0x20], vec![0xAB; 32]].concat());
+        assert_eq!(p2wsh.segwit_scriptcode(), None);
+    }
+
+    #[test]
+    fn test_witness_sigops() {
+        let multisig = multisig_script(2, &[0x02, 0x02, 0x02]);
+        assert_eq!(multisig.witness_sigops(), 3);
+        assert_eq!(multisig.sigop_count(), 20);
+
+        let checksig = Script::new([vec![33], vec![0x02; 33], vec![0xAC]].concat());
+        assert_eq!(checksig.witness_sigops(), 1);
+    }
+
+    #[test]
+    fn test_input_weight() {
+        assert_eq!(input_weight(ScriptType::P2pkh), Some(592));
+        assert_eq!(input_weight(ScriptType::P2sh), Some(364));
+        assert_eq!(input_weight(ScriptType::P2wpkh), Some(272));
+        assert_eq!(input_weight(ScriptType::P2tr), Some(230));
+        assert_eq!(input_weight(ScriptType::P2wsh), None);
+        assert_eq!(input_weight(ScriptType::Multisig), None);
+        assert_eq!(input_weight(ScriptType::NonStandard), None);
+    }
+
+    #[test]
+    fn test_is_fully_segwit() {
+        let input = input_with_script(0);
+        let tx = BitcoinTransaction::new(2, vec![input.clone(), input], 0);
+        let p2wpkh = Script::new([vec![0x00, 0x14], vec![0xAA; 20]].concat());
+        let p2tr = Script::new([vec![0x51, 0x20], vec![0xBB; 32]].concat());
+        let p2pkh =
+            Script::new([vec![0x76, 0xA9, 0x14], vec![0xCC; 20], vec![0x88, 0xAC]].concat());
+
+        assert!(tx.is_fully_segwit(&[p2wpkh.clone(), p2tr]));
+        assert!(!tx.is_fully_segwit(&[p2wpkh.clone(), p2pkh]));
+        assert!(!tx.is_fully_segwit(&[p2wpkh]));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_verify_witness_shape() {
+        let pubkey =
+            hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+                .unwrap();
+        let p2wpkh =
+            Script::new(hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap());
+        let well_formed = Witness::new(vec![vec![0x30; 71], pubkey.clone()]);
+        assert_eq!(verify_witness_shape(&well_formed, &p2wpkh), Ok(()));
+
+        let malformed = [
+            Witness::new(vec![pubkey.clone()]),
+            Witness::new(vec![vec![0x30; 71], pubkey.clone(), vec![0x51]]),
+            Witness::new(vec![vec![0x30; 71], vec![0x02; 33]]),
+            Witness::new(vec![vec![0x01; 64], pubkey]),
+        ];
+        for witness in &malformed {
+            assert_eq!(
+                verify_witness_shape(witness, &p2wpkh),
+                Err(BitcoinError::InvalidFormat)
+            );
+        }
+
+        let p2wsh = Script::new(
+            hex::decode("00204ae81572f06e1b88fd5ced7a1a000945432e83e1551e6f721ee9c00b8cc33260")
+                .unwrap(),
+        );
+        let op_true = Witness::new(vec![vec![0x51]]);
+        assert_eq!(verify_witness_shape(&op_true, &p2wsh), Ok(()));
+        assert_eq!(
+            verify_witness_shape(&Witness::new(vec![vec![0x52]]), &p2wsh),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        let p2tr = Script::new([vec![0x51, 0x20], vec![0xAA; 32]].concat());
+        let key_path = Witness::new(vec![vec![0x01; 64]]);
+        assert_eq!(verify_witness_shape(&key_path, &p2tr), Ok(()));
+        let script_path = Witness::new(vec![vec![0x51], vec![0xC0; 33]]);
+        assert_eq!(verify_witness_shape(&script_path, &p2tr), Ok(()));
+        let bad_control_block = Witness::new(vec![vec![0x51], vec![0xC0; 40]]);
+        assert_eq!(
+            verify_witness_shape(&bad_control_block, &p2tr),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        assert_eq!(
+            verify_witness_shape(&Witness::default(), &p2pkh_script()),
+            Ok(())
+        );
+        assert_eq!(
+            verify_witness_shape(&key_path, &p2pkh_script()),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_tagged_hash() {
+        let script =
+            hex::decode("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac")
+                .unwrap();
+        let mut leaf = vec![0xC0, script.len() as u8];
+        leaf.extend_from_slice(&script);
+        assert_eq!(
+            hex::encode(tagged_hash("TapLeaf", &leaf)),
+            "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"
+        );
+        assert_eq!(
+            hex::encode(tagged_hash("TapSighash", &[])),
+            "dabc11914abcd8072900042a2681e52f8dba99ce82e224f97b5fdb7cd4b9c803"
+        );
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_tapleaf_hash() {
+        let script = Script::new(
+            hex::decode("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac")
+                .unwrap(),
+        );
+        assert_eq!(
+            hex::encode(script.tapleaf_hash(0xC0)),
+            "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"
+        );
+        assert_ne!(script.tapleaf_hash(0xC2), script.tapleaf_hash(0xC0));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_p2sh_matches() {
+        // P2SH of the one-byte OP_TRUE redeem script.
+        let p2sh =
+            Script::new(hex::decode("a914da1745e9b549bd0bfa1a569971c77eba30cd5a4b87").unwrap());
+        let matching = Script::new(vec![0x01, 0x51]);
+        assert!(Script::p2sh_matches(&matching, &p2sh));
+
+        let mismatching = Script::new(vec![0x01, 0x52]);
+        assert!(!Script::p2sh_matches(&mismatching, &p2sh));
+
+        let not_push_only = Script::new(vec![0x76, 0x01, 0x51]);
+        assert!(!Script::p2sh_matches(&not_push_only, &p2sh));
+        assert!(!Script::p2sh_matches(&matching, &p2pkh_script()));
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_verify_taproot_script_path() {
+        let key = |s: &str| {
+            let mut k = [0u8; 32];
+            k.copy_from_slice(&hex::decode(s).unwrap());
+            k
+        };
+
+        // BIP-341 wallet test vector: a single-leaf tree.
+        let output_key = key("147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3");
+        let script = Script::new(
+            hex::decode("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac")
+                .unwrap(),
+        );
+        let control_block =
+            hex::decode("c1187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27")
+                .unwrap();
+        assert_eq!(
+            verify_taproot_script_path(&output_key, &control_block, &script),
+            Ok(true)
+        );
+
+        let mut wrong_parity = control_block.clone();
+        wrong_parity[0] = 0xC0;
+        assert_eq!(
+            verify_taproot_script_path(&output_key, &wrong_parity, &script),
+            Ok(false)
+        );
+
+        // Two-leaf tree: spending the first leaf with the second leaf's hash as the path.
+        let output_key = key("24358839ca3f148348371fe0ec6ee72b4d65592370a069e89b927d668c53c4f6");
+        let leaf_a = Script::new(
+            hex::decode("20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac")
+                .unwrap(),
+        );
+        let leaf_b = Script::new(
+            hex::decode("2071981521ad9fc9036687364118fb6ccd2035b96a423c59c5430e98310a11abe2ac")
+                .unwrap(),
+        );
+        let mut control_block =
+            hex::decode("c093478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820")
+                .unwrap();
+        control_block.extend_from_slice(&leaf_b.tapleaf_hash(0xC0));
+        assert_eq!(
+            verify_taproot_script_path(&output_key, &control_block, &leaf_a),
+            Ok(true)
+        );
+        assert_eq!(
+            verify_taproot_script_path(&output_key, &control_block, &leaf_b),
+            Ok(false)
+        );
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_taproot_merkle_root() {
+        let leaf_a = Script::new(
+            hex::decode("20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac")
+                .unwrap(),
+        );
+        let leaf_b = Script::new(
+            hex::decode("2071981521ad9fc9036687364118fb6ccd2035b96a423c59c5430e98310a11abe2ac")
+                .unwrap(),
+        );
+
+        let two = [(0xC0, leaf_a.clone()), (0xC0, leaf_b.clone())];
+        assert_eq!(
+            hex::encode(taproot_merkle_root(&two)),
+            "451bcf83d02b56af9d218fd7936ed9623415f74947b7789374f67229335dfa1e"
+        );
+        let swapped = [(0xC0, leaf_b.clone()), (0xC0, leaf_a.clone())];
+        assert_eq!(taproot_merkle_root(&swapped), taproot_merkle_root(&two));
+
+        let three = [
+            (0xC0, leaf_a.clone()),
+            (0xC0, leaf_b),
+            (0xC0, Script::new(vec![0x51])),
+        ];
+        assert_eq!(
+            hex::encode(taproot_merkle_root(&three)),
+            "e7cca0d43e7695c75f4c297473a4830d5e8f66b212312d015d698a8bd87ccbbe"
+        );
+
+        assert_eq!(
+            taproot_merkle_root(&[(0xC0, leaf_a.clone())]),
+            leaf_a.tapleaf_hash(0xC0)
+        );
+        assert_eq!(taproot_merkle_root(&[]), [0u8; 32]);
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_taproot_tweak_pubkey() {
+        let key = |s: &str| {
+            let mut k = [0u8; 32];
+            k.copy_from_slice(&hex::decode(s).unwrap());
+            k
+        };
+
+        // BIP-341 wallet test vectors: key-path only, and a single-leaf tree.
+        let internal = key("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        let (tweaked, odd) = taproot_tweak_pubkey(&internal, None).unwrap();
+        assert_eq!(
+            hex::encode(tweaked),
+            "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343"
+        );
+        assert!(odd);
+
+        let internal = key("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27");
+        let root = key("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21");
+        let (tweaked, odd) = taproot_tweak_pubkey(&internal, Some(root)).unwrap();
+        assert_eq!(
+            hex::encode(tweaked),
+            "147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3"
+        );
+        assert!(odd);
+
+        assert_eq!(
+            taproot_tweak_pubkey(&[0xFF; 32], None),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_spend_requirements() {
+        let p2pkh = p2pkh_script();
+        let req = p2pkh.spend_requirements();
+        assert_eq!(req.signatures, 1);
+        assert!(!req.needs_redeem_script && !req.needs_witness_script);
+        assert!(!req.timelocked && req.spendable);
+
+        assert_eq!(
+            multisig_script(2, &[0x02, 0x03, 0x02])
+                .spend_requirements()
+                .signatures,
+            2
+        );
+
+        let p2sh =
+            Script::new(hex::decode("a914748284390f9e263a4b766a75d0633c50426eb87587").unwrap());
+        assert!(p2sh.spend_requirements().needs_redeem_script);
+    }
+
+    #[test]
+    fn test_cluster_by_common_input_ownership() {
+        let input =
+            |n: u8| TransactionInput::new(OutPoint::new(dummy_txid(n), 0), Script::new(vec![]), 0);
+        let txs = vec![
+            BitcoinTransaction::new(2, vec![input(1), input(2)], 0),
+            BitcoinTransaction::new(2, vec![input(3)], 0),
+            BitcoinTransaction::new(2, vec![input(2), input(4)], 0),
+            BitcoinTransaction::new(2, vec![input(4)], 0),
+        ];
+        assert_eq!(
+            cluster_by_common_input_ownership(&txs),
+            vec![vec![0, 2, 3], vec![1]]
+        );
+        assert!(cluster_by_common_input_ownership(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_with_all_sequences() {
+        let input = |n: u8| {
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(n), n as u32),
+                Script::new(vec![n]),
+                0xFFFFFFFF,
+            )
+        };
+        let tx = BitcoinTransaction::new(2, vec![input(1), input(2)], 800_000);
+        let rbf = tx.with_all_sequences(0xFFFFFFFD);
+        assert!(rbf.inputs.iter().all(|i| i.sequence == 0xFFFFFFFD));
+        assert_eq!(rbf.version, tx.version);
+        assert_eq!(rbf.lock_time, tx.lock_time);
+        for (a, b) in rbf.inputs.iter().zip(&tx.inputs) {
+            assert_eq!(a.previous_output, b.previous_output);
+            assert_eq!(a.script_sig, b.script_sig);
+        }
+        assert!(tx.inputs.iter().all(|i| i.sequence == 0xFFFFFFFF));
+    }
+
+    #[test]
+    fn test_input_type_uniformity() {
+        let input = input_with_script(0);
+        let tx = BitcoinTransaction::new(2, vec![input.clone(), input], 0);
+        let p2wpkh = |b: u8| Script::new([vec![0x00, 0x14], vec![b; 20]].concat());
+        let p2tr = Script::new([vec![0x51, 0x20], vec![0xBB; 32]].concat());
+
+        assert!(tx.input_type_uniformity(&[p2wpkh(0xAA), p2wpkh(0xCC)]));
+        assert!(!tx.input_type_uniformity(&[p2wpkh(0xAA), p2tr]));
+        assert!(!tx.input_type_uniformity(&[p2wpkh(0xAA)]));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_content_hash() {
+        let input =
+            |n: u8| TransactionInput::new(OutPoint::new(dummy_txid(n), 0), Script::new(vec![n]), 0);
+        let tx = BitcoinTransaction::new(2, vec![input(1), input(2)], 0);
+        let swapped = BitcoinTransaction::new(2, vec![input(2), input(1)], 0);
+        assert_ne!(tx.to_bytes(), swapped.to_bytes());
+        assert_eq!(tx.content_hash(), swapped.content_hash());
+
+        let other = BitcoinTransaction::new(2, vec![input(1), input(3)], 0);
+        assert_ne!(tx.content_hash(), other.content_hash());
+
+        let pay = TransactionOutput::new(40_000, p2pkh_script());
+        let change = TransactionOutput::new(9_321, Script::new(vec![0x51]));
+        let mut paid = tx.clone();
+        paid.outputs = vec![pay.clone(), change.clone()];
+        let mut reordered = swapped.clone();
+        reordered.outputs = vec![change, pay];
+        assert_eq!(paid.content_hash(), reordered.content_hash());
+        assert_ne!(paid.content_hash(), tx.content_hash());
+    }
+
+    #[test]
+    fn test_uses_antifeesnipe_locktime() {
+        let input = TransactionInput {
+            sequence: 0xFFFFFFFD,
+            ..input_with_script(0)
+        };
+        let tip = 850_000;
+        assert!(
+            BitcoinTransaction::new(2, vec![input.clone()], tip).uses_antifeesnipe_locktime(tip)
+        );
+        assert!(
+            BitcoinTransaction::new(2, vec![input.clone()], tip - 7)
+                .uses_antifeesnipe_locktime(tip)
+        );
+        assert!(
+            !BitcoinTransaction::new(2, vec![input.clone()], 0).uses_antifeesnipe_locktime(tip)
+        );
+        assert!(
+            !BitcoinTransaction::new(2, vec![input.clone()], 700_000)
+                .uses_antifeesnipe_locktime(tip)
+        );
+        assert!(!BitcoinTransaction::new(2, vec![input], tip + 1).uses_antifeesnipe_locktime(tip));
+    }
+
+    #[test]
+    fn test_check_descendant_limits() {
+        #[cfg(feature = "hashes")]
+        fn spending(tx: &BitcoinTransaction) -> OutPoint {
+            OutPoint::new(tx.txid().0, 0)
+        }
+        #[cfg(not(feature = "hashes"))]
+        fn spending(_: &BitcoinTransaction) -> OutPoint {
+            OutPoint::new(dummy_txid(1), 0)
+        }
+
+        let parent = BitcoinTransaction::new(2, vec![input_with_script(58)], 0);
+        let mut chain: Vec<BitcoinTransaction> = Vec::new();
+        for _ in 0..3 {
+            let input = TransactionInput {
+                previous_output: spending(chain.last().unwrap_or(&parent)),
+                ..input_with_script(58)
+            };
+            chain.push(BitcoinTransaction::new(2, vec![input], 0));
+        }
+
+        assert_eq!(
+            check_descendant_limits(&parent, &chain, 25, 101_000),
+            Ok(())
+        );
+        assert_eq!(
+            check_descendant_limits(&parent, &chain, 3, 101_000),
+            Err(BitcoinError::LimitExceeded)
+        );
+        assert_eq!(check_descendant_limits(&parent, &chain, 25, 436), Ok(()));
+        assert_eq!(
+            check_descendant_limits(&parent, &chain, 25, 435),
+            Err(BitcoinError::LimitExceeded)
+        );
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_check_descendant_limits_requires_linkage() {
+        let parent = BitcoinTransaction::new(2, vec![input_with_script(58)], 0);
+        let spend = TransactionInput {
+            previous_output: OutPoint::new(parent.txid().0, 0),
+            ..input_with_script(0)
+        };
+        let child = BitcoinTransaction::new(2, vec![spend], 0);
+        let unrelated = BitcoinTransaction::new(2, vec![input_with_script(0)], 0);
+
+        let linked = vec![child];
+        assert_eq!(
+            check_descendant_limits(&parent, &linked, 25, 101_000),
+            Ok(())
+        );
+        let with_unrelated = [linked, vec![unrelated]].concat();
+        assert_eq!(
+            check_descendant_limits(&parent, &with_unrelated, 25, 101_000),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_validate_many() {
+        let outpoint = |n: u8| (OutPoint::new(dummy_txid(n), 0), 0xFFFFFFFF);
+        let pay = |value: u64| (value, p2pkh_script());
+        let valid = BitcoinTransaction::simple(vec![outpoint(1)], vec![pay(50_000)]);
+        let no_inputs = BitcoinTransaction::simple(Vec::new(), vec![pay(50_000)]);
+        let no_outputs = BitcoinTransaction::simple(vec![outpoint(1)], Vec::new());
+        let duplicate =
+            BitcoinTransaction::simple(vec![outpoint(1), outpoint(1)], vec![pay(50_000)]);
+        let too_large_value =
+            BitcoinTransaction::simple(vec![outpoint(1)], vec![pay(2_100_000_000_000_001)]);
+        let too_large_total = BitcoinTransaction::simple(
+            vec![outpoint(1)],
+            vec![pay(2_000_000_000_000_000), pay(200_000_000_000_000)],
+        );
+        let overflowing = BitcoinTransaction::simple(vec![outpoint(1)], vec![pay(u64::MAX); 2]);
+        let mut oversized = valid.clone();
+        oversized.inputs[0].script_sig = Script::new(vec![0x00; 1_000_000]);
+
+        assert_eq!(
+            validate_many(&[
+                valid,
+                no_inputs,
+                no_outputs,
+                duplicate,
+                too_large_value,
+                too_large_total,
+                overflowing,
+                oversized,
+            ]),
+            vec![
+                Ok(()),
+                Err(BitcoinError::InvalidFormat),
+                Err(BitcoinError::InvalidFormat),
+                Err(BitcoinError::InvalidFormat),
+                Err(BitcoinError::LimitExceeded),
+                Err(BitcoinError::LimitExceeded),
+                Err(BitcoinError::LimitExceeded),
+                Err(BitcoinError::LimitExceeded),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mempool_entry_eviction_order() {
+        let input = input_with_script(58);
+        let tx = BitcoinTransaction::new(2, vec![input], 0);
+        let high = MempoolEntry::new(tx.clone(), 1090, 10);
+        let low = MempoolEntry::new(tx.clone(), 218, 30);
+        let low_older = MempoolEntry::new(tx, 218, 20);
+        assert_eq!(high.fee_rate, 10.0);
+        assert_eq!(low.fee_rate, 2.0);
+
+        let mut entries = vec![high.clone(), low.clone(), low_older.clone()];
+        entries.sort_by(MempoolEntry::eviction_cmp);
+        assert_eq!(entries, vec![low_older, low, high]);
+    }
+
+    #[test]
+    fn test_size_with_additional_input() {
+        let input = input_with_script(107);
+        let tx = BitcoinTransaction::new(2, vec![input.clone()], 0);
+        let grown = BitcoinTransaction::new(2, vec![input.clone(), input.clone()], 0);
+        assert_eq!(
+            tx.size_with_additional_input(ScriptType::P2pkh),
+            Some(grown.vsize())
+        );
+        assert_eq!(
+            tx.size_with_additional_input(ScriptType::P2pkh),
+            Some(tx.vsize() + input.to_bytes().len())
+        );
+
+        let full = BitcoinTransaction::new(2, vec![input.clone(); 252], 0);
+        let over = BitcoinTransaction::new(2, vec![input; 253], 0);
+        assert_eq!(
+            full.size_with_additional_input(ScriptType::P2pkh),
+            Some(over.vsize())
+        );
+        assert_eq!(tx.size_with_additional_input(ScriptType::P2wsh), None);
+
+        // 189-byte legacy P2PKH spend plus a P2WPKH input: 230 base bytes and
+        // 111 witness bytes (marker, flag, an empty and a 108-byte witness)
+        // make 1031 WU.
+        let mut legacy = BitcoinTransaction::new(2, vec![input_with_script(107)], 0);
+        legacy.outputs.push(TransactionOutput::new(
+            50_000,
+            Script::new([vec![0x00, 0x14], vec![0xAA; 20]].concat()),
+        ));
+        assert_eq!(legacy.to_bytes().len(), 189);
+        assert_eq!(
+            legacy.size_with_additional_input(ScriptType::P2wpkh),
+            Some(258)
+        );
+    }
+
+    #[test]
+    fn test_vsize_ceil() {
+        let input = input_with_script(58);
+        let tx = BitcoinTransaction::new(2, vec![input], 0);
+        assert_eq!(tx.weight(), 436);
+        assert_eq!(tx.vsize_ceil(), 109);
+        assert_eq!(tx.vsize_ceil(), tx.vsize());
+    }
+
+    #[test]
+    fn test_cltv_constraint() {
+        // <800000> OP_CHECKLOCKTIMEVERIFY OP_DROP <pubkey> OP_CHECKSIG
+        let mut bytes = vec![0x03, 0x00, 0x35, 0x0C, 0xB1, 0x75, 0x21];
+        bytes.extend_from_slice(&[0x02; 33]);
+        bytes.push(0xAC);
+        assert_eq!(Script::new(bytes).cltv_constraint(), Some(800_000));
+
+        assert_eq!(
+            Script::new(vec![0x5A, 0xB1, 0x75]).cltv_constraint(),
+            Some(10)
+        );
+        assert_eq!(
+            Script::new(vec![0x01, 0x81, 0xB1, 0x75]).cltv_constraint(),
+            None
+        );
+        assert_eq!(
+            Script::new(vec![0x03, 0x00, 0x35, 0x0C, 0xB2, 0x75]).cltv_constraint(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_csv_constraint() {
+        // to_local style: <144> OP_CHECKSEQUENCEVERIFY OP_DROP <pubkey> OP_CHECKSIG
+        let mut bytes = vec![0x02, 0x90, 0x00, 0xB2, 0x75, 0x21];
+        bytes.extend_from_slice(&[0x03; 33]);
+        bytes.push(0xAC);
+        let script = Script::new(bytes);
+        assert_eq!(script.csv_constraint(), Some(144));
+        assert_eq!(script.cltv_constraint(), None);
+
+        assert_eq!(
+            Script::new(vec![0x60, 0xB2, 0x75]).csv_constraint(),
+            Some(16)
+        );
+        assert_eq!(Script::new(vec![0x60, 0xB2]).csv_constraint(), None);
+    }
+
+    #[test]
+    fn test_is_htlc() {
+        // OP_IF OP_HASH160 <hash> OP_EQUALVERIFY <pk> OP_ELSE <locktime> OP_CLTV OP_DROP <pk> OP_ENDIF OP_CHECKSIG
+        let mut bytes = vec![0x63, 0xA9, 0x14];
+        bytes.extend_from_slice(&[0xAB; 20]);
+        bytes.extend_from_slice(&[0x88, 0x21]);
+        bytes.extend_from_slice(&[0x02; 33]);
+        bytes.extend_from_slice(&[0x67, 0x03, 0x00, 0x35, 0x0C, 0xB1, 0x75, 0x21]);
+        bytes.extend_from_slice(&[0x03; 33]);
+        bytes.extend_from_slice(&[0x68, 0xAC]);
+        assert!(Script::new(bytes).is_htlc());
+
+        let p2pkh = p2pkh_script();
+        assert!(!p2pkh.is_htlc());
+
+        let mut no_timelock = vec![0x63, 0xA9, 0x14];
+        no_timelock.extend_from_slice(&[0xAB; 20]);
+        no_timelock.extend_from_slice(&[0x88, 0x67, 0x68, 0x51]);
+        assert!(!Script::new(no_timelock).is_htlc());
+    }
+
+    #[test]
+    fn test_tapscript_spend_weight() {
+        let mut bytes = vec![0x20];
+        bytes.extend_from_slice(&[0xD8; 32]);
+        bytes.push(0xAC);
+        let script = Script::new(bytes);
+        assert_eq!(script.tapscript_spend_weight(1), 1 + 65 + 35 + 34);
+        assert_eq!(script.tapscript_spend_weight(0), 1 + 35 + 34);
+    }
+
+    #[test]
+    fn test_psbt_round_trip() {
+        let input = |n: u8| {
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(n), 0),
+                Script::new(vec![]),
+                0xFFFFFFFD,
+            )
+        };
+        let tx = BitcoinTransaction::new(2, vec![input(1), input(2)], 0);
+        let mut psbt = PartiallySignedTransaction::new(tx);
+        psbt.inputs[0].prevout_script = Some(p2pkh_script());
+        psbt.inputs[0]
+            .partial_sigs
+            .push((vec![0x02; 33], vec![0x30; 71]));
+
+        let bytes = psbt.to_bytes();
+        let (parsed, consumed) = PartiallySignedTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(parsed, psbt);
+        assert!(parsed.inputs[1].partial_sigs.is_empty());
+        assert_eq!(parsed.inputs[1].prevout_script, None);
+
+        assert_eq!(
+            PartiallySignedTransaction::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(BitcoinError::InsufficientBytes)
+        );
+
+        let huge_len = [0xFF; 9];
+        assert_eq!(
+            Script::from_bytes(&huge_len),
+            Err(BitcoinError::InsufficientBytes)
+        );
+        let mut crafted = BitcoinTransaction::new(2, vec![input(1)], 0).to_bytes();
+        crafted.extend_from_slice(&[0x00, 0x01]);
+        crafted.extend_from_slice(&huge_len);
+        assert_eq!(
+            PartiallySignedTransaction::from_bytes(&crafted),
+            Err(BitcoinError::InsufficientBytes)
+        );
+    }
+
+    #[test]
+    fn test_psbt_combine() {
+        let input = input_with_script(0);
+        let tx = BitcoinTransaction::new(2, vec![input], 0);
+        let multisig = multisig_script(2, &[0x02, 0x03]);
+
+        let mut alice = PartiallySignedTransaction::new(tx.clone());
+        alice.inputs[0].prevout_script = Some(multisig);
+        alice.inputs[0]
+            .partial_sigs
+            .push((vec![0x02; 33], vec![0x30, 0x01]));
+        let mut bob = PartiallySignedTransaction::new(tx.clone());
+        bob.inputs[0]
+            .partial_sigs
+            .push((vec![0x03; 33], vec![0x30, 0x02]));
+
+        alice.combine(&bob).unwrap();
+        assert_eq!(
+            alice.inputs[0].partial_sigs,
+            vec![
+                (vec![0x02; 33], vec![0x30, 0x01]),
+                (vec![0x03; 33], vec![0x30, 0x02])
+            ]
+        );
+        alice.combine(&bob).unwrap();
+        assert_eq!(alice.inputs[0].partial_sigs.len(), 2);
+
+        let mut other_script = PartiallySignedTransaction::new(tx.clone());
+        other_script.inputs[0].prevout_script = Some(p2pkh_script());
+        assert_eq!(
+            alice.combine(&other_script),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        let mut other_sig = PartiallySignedTransaction::new(tx.clone());
+        other_sig.inputs[0]
+            .partial_sigs
+            .push((vec![0x04; 33], vec![0x30, 0x04]));
+        other_sig.inputs[0]
+            .partial_sigs
+            .push((vec![0x03; 33], vec![0x30, 0x03]));
+        let before = alice.clone();
+        assert_eq!(alice.combine(&other_sig), Err(BitcoinError::InvalidFormat));
+        assert_eq!(alice, before);
+
+        let other = PartiallySignedTransaction::new(BitcoinTransaction::new(1, tx.inputs, 0));
+        assert_eq!(alice.combine(&other), Err(BitcoinError::InvalidFormat));
+    }
+
+    #[test]
+    fn test_psbt_finalize() {
+        let input = input_with_script(0);
+        let tx = BitcoinTransaction::new(2, vec![input], 0);
+        let mut psbt = PartiallySignedTransaction::new(tx);
+        assert_eq!(psbt.finalize(), Err(BitcoinError::Incomplete));
+
+        psbt.inputs[0].prevout_script =
+            Some(Script::new([vec![33], vec![0x03; 33], vec![0xAC]].concat()));
+        assert_eq!(psbt.finalize(), Err(BitcoinError::Incomplete));
+        psbt.inputs[0]
+            .partial_sigs
+            .push((vec![0x03; 33], vec![0x30; 71]));
+        let signed = psbt.finalize().unwrap();
+
+        let mut expected = vec![71];
+        expected.extend_from_slice(&[0x30; 71]);
+        assert_eq!(signed.inputs[0].script_sig, Script::new(expected));
+        assert_eq!(signed.version, psbt.unsigned_tx.version);
+        assert_eq!(
+            signed.inputs[0].previous_output,
+            psbt.unsigned_tx.inputs[0].previous_output
+        );
+
+        let mut missing = psbt;
+        missing.inputs.clear();
+        assert_eq!(missing.finalize(), Err(BitcoinError::InvalidFormat));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_psbt_finalize_p2pkh() {
+        let tx = BitcoinTransaction::new(2, vec![input_with_script(0)], 0);
+        let mut psbt = PartiallySignedTransaction::new(tx);
+        psbt.inputs[0].prevout_script = Some(p2pkh_script());
+        assert_eq!(psbt.finalize(), Err(BitcoinError::Incomplete));
+
+        let unrelated = (vec![0x02; 33], vec![0x30; 71]);
+        psbt.inputs[0].partial_sigs.push(unrelated);
+        assert_eq!(psbt.finalize(), Err(BitcoinError::Incomplete));
+
+        let pubkey =
+            hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+                .unwrap();
+        let sig = vec![0x30; 72];
+        psbt.inputs[0]
+            .partial_sigs
+            .push((pubkey.clone(), sig.clone()));
+        let signed = psbt.finalize().unwrap();
+
+        let mut expected = vec![72];
+        expected.extend_from_slice(&sig);
+        expected.push(33);
+        expected.extend_from_slice(&pubkey);
+        assert_eq!(signed.inputs[0].script_sig, Script::new(expected));
+    }
+
+    #[test]
+    fn test_detect_nonce_reuse() {
+        let spend = |n: u8, sig: &str| {
+            let sig = hex::decode(sig).unwrap();
+            let mut bytes = vec![sig.len() as u8];
+            bytes.extend_from_slice(&sig);
+            bytes.push(33);
+            bytes.extend_from_slice(&[0x02; 33]);
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(n), 0),
+                Script::new(bytes),
+                0xFFFFFFFF,
+            )
+        };
+        let txs = vec![
+            BitcoinTransaction::new(2, vec![spend(1, "300602011102010201")], 0),
+            BitcoinTransaction::new(
+                2,
+                vec![
+                    spend(2, "300602012202010201"),
+                    spend(3, "300602011102010301"),
+                ],
+                0,
+            ),
+        ];
+        assert_eq!(
+            detect_nonce_reuse(&txs),
+            vec![(
+                OutPoint::new(dummy_txid(1), 0),
+                OutPoint::new(dummy_txid(3), 0)
+            )]
+        );
+        assert!(detect_nonce_reuse(&txs[1..]).is_empty());
+    }
+
+    #[test]
+    fn test_relay_priority() {
+        let input = input_with_script(58);
+        let tx = BitcoinTransaction::new(2, vec![input.clone()], 0);
+        assert!(tx.relay_priority(2000) > tx.relay_priority(1000));
+        assert!(tx.relay_priority(1090) < 10.0);
+        assert!(tx.relay_priority(1090) > 9.9);
+
+        let nonstandard = BitcoinTransaction::new(4, vec![input], 0);
+        assert_eq!(nonstandard.relay_priority(2000), 0.0);
+    }
+
+    #[test]
+    fn test_output_script_bytes_total() {
+        let mut tx = BitcoinTransaction::new(2, vec![input_with_script(0)], 0);
+        assert_eq!(tx.output_script_bytes_total(), 0);
+        tx.outputs = vec![
+            TransactionOutput::new(10_000, p2pkh_script()),
+            TransactionOutput::new(
+                20_000,
+                Script::new([vec![0x51, 0x20], vec![0xBB; 32]].concat()),
+            ),
+            TransactionOutput::new(0, Script::new(vec![0x6A, 0x01, 0xFF])),
+        ];
+        assert_eq!(tx.output_script_bytes_total(), 25 + 34 + 3);
+
+        let (parsed, consumed) = BitcoinTransaction::from_bytes(&tx.to_bytes()).unwrap();
+        assert_eq!(parsed, tx);
+        assert_eq!(consumed, tx.to_bytes().len());
+    }
+
+    #[test]
+    fn test_simple_transaction() {
+        let tx = BitcoinTransaction::simple(
+            vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFD)],
+            vec![(50_000, p2pkh_script()), (1_000, Script::new(vec![0x51]))],
+        );
+        assert_eq!(tx.version, 2);
+        assert_eq!(tx.lock_time, 0);
+        assert!(tx.inputs[0].script_sig.is_empty());
+        assert_eq!(tx.inputs[0].sequence, 0xFFFFFFFD);
+        assert_eq!(
+            tx.outputs[1],
+            TransactionOutput::new(1_000, Script::new(vec![0x51]))
+        );
+
+        let bytes = tx.to_bytes();
+        let (parsed, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, tx);
+        assert_eq!(consumed, bytes.len());
+    }
+
+    #[test]
+    fn test_has_reused_output_scripts() {
+        let op_true = Script::new(vec![0x51]);
+        let reused = BitcoinTransaction::simple(
+            vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+            vec![
+                (1_000, p2pkh_script()),
+                (2_000, op_true.clone()),
+                (3_000, p2pkh_script()),
+            ],
+        );
+        assert!(reused.has_reused_output_scripts());
+
+        let distinct = BitcoinTransaction::simple(
+            vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+            vec![(1_000, p2pkh_script()), (2_000, op_true)],
+        );
+        assert!(!distinct.has_reused_output_scripts());
+    }
+
+    #[test]
+    fn test_equal_output_groups() {
+        let op_true = || Script::new(vec![0x51]);
+        let tx = BitcoinTransaction::simple(
+            vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+            vec![
+                (100_000, op_true()),
+                (42_137, op_true()),
+                (100_000, op_true()),
+                (100_000, op_true()),
+            ],
+        );
+        assert_eq!(tx.equal_output_groups(), vec![(100_000, 3), (42_137, 1)]);
+        assert!(
+            BitcoinTransaction::new(2, vec![], 0)
+                .equal_output_groups()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_tx_pattern() {
+        let tx = |inputs: u8, values: &[u64]| {
+            BitcoinTransaction::simple(
+                (1..=inputs)
+                    .map(|n| (OutPoint::new(dummy_txid(n), 0), 0xFFFFFFFF))
+                    .collect(),
+                values
+                    .iter()
+                    .map(|&v| (v, Script::new(vec![0x51])))
+                    .collect(),
+            )
+        };
+        assert_eq!(tx(1, &[50_000, 12_345]).tx_pattern(), TxPattern::Payment);
+        assert_eq!(tx(5, &[90_000]).tx_pattern(), TxPattern::Consolidation);
+        assert_eq!(
+            tx(1, &[1_000, 2_000, 3_000, 4_000]).tx_pattern(),
+            TxPattern::Batch
+        );
+        assert_eq!(
+            tx(3, &[100_000, 100_000, 100_000, 7_000]).tx_pattern(),
+            TxPattern::CoinJoin
+        );
+        assert_eq!(tx(3, &[1_000, 2_000]).tx_pattern(), TxPattern::Other);
+    }
+
+    #[test]
+    fn test_input_output_script_overlap() {
+        let other = Script::new([vec![0x00, 0x14], vec![0xAA; 20]].concat());
+        let tx = BitcoinTransaction::simple(
+            vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+            vec![(40_000, other.clone()), (9_000, p2pkh_script())],
+        );
+        assert!(tx.input_output_script_overlap(&[p2pkh_script()]));
+        assert!(!tx.input_output_script_overlap(&[Script::new(vec![0x51])]));
+    }
+
+    #[test]
+    fn test_has_nonstandard_outputs() {
+        let tx = |script: Script| {
+            BitcoinTransaction::simple(
+                vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+                vec![(10_000, p2pkh_script()), (1_000, script)],
+            )
+        };
+        assert!(!tx(p2pkh_script()).has_nonstandard_outputs());
+        assert!(tx(multisig_script(1, &[0x02, 0x03])).has_nonstandard_outputs());
+        assert!(tx(Script::new(vec![0xAC])).has_nonstandard_outputs());
+
+        let op_return =
+            |len: usize| Script::new([vec![0x6A, 0x4C, len as u8], vec![0xEE; len]].concat());
+        assert!(!tx(op_return(80)).has_nonstandard_outputs());
+        assert!(tx(op_return(81)).has_nonstandard_outputs());
+    }
+
+    #[test]
+    fn test_dust_outputs() {
+        let p2wpkh = Script::new([vec![0x00, 0x14], vec![0xAA; 20]].concat());
+        // Core's default 3000 sat/kvB gives the familiar 546 and 294 sat thresholds.
+        assert!(TransactionOutput::new(545, p2pkh_script()).is_dust(3000));
+        assert!(!TransactionOutput::new(546, p2pkh_script()).is_dust(3000));
+        assert!(TransactionOutput::new(293, p2wpkh.clone()).is_dust(3000));
+        assert!(!TransactionOutput::new(294, p2wpkh.clone()).is_dust(3000));
+        assert!(!TransactionOutput::new(0, Script::new(vec![0x6A])).is_dust(3000));
+        assert!(!TransactionOutput::new(0, Script::new(vec![0x6A, 0xAC])).is_dust(3000));
+
+        // 182 bytes at 1001 sat/kvB is 182.182 sat, which Core rounds up to 183.
+        assert!(TransactionOutput::new(182, p2pkh_script()).is_dust(1001));
+        assert!(!TransactionOutput::new(183, p2pkh_script()).is_dust(1001));
+
+        // Future witness versions get the same discount as v0 programs.
+        let v2_program = Script::new([vec![0x52, 0x14], vec![0xBB; 20]].concat());
+        assert!(TransactionOutput::new(293, v2_program.clone()).is_dust(3000));
+        assert!(!TransactionOutput::new(294, v2_program).is_dust(3000));
+
+        let tx = BitcoinTransaction::simple(
+            vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+            vec![(50_000, p2pkh_script()), (100, p2wpkh)],
+        );
+        assert_eq!(tx.dust_outputs(3000), vec![1]);
+    }
+
+    #[test]
+    fn test_effective_value() {
+        let mut tx = BitcoinTransaction::new(2, vec![input_with_script(48)], 0);
+        tx.outputs
+            .push(TransactionOutput::new(5_000, Script::new(vec![0x51])));
+        assert_eq!(tx.vsize(), 109);
+        assert_eq!(tx.effective_value(10), 5_000 - 1_090);
+        assert_eq!(tx.effective_value(100), 5_000 - 10_900);
+        assert!(tx.effective_value(100) < 0);
+        assert_eq!(tx.effective_value(u64::MAX), i64::MIN);
+
+        let mut large = tx.clone();
+        large.outputs = vec![TransactionOutput::new(u64::MAX, Script::new(vec![0x51])); 2];
+        assert_eq!(large.effective_value(0), i64::MAX);
+    }
+
+    #[test]
+    fn test_looks_like_payjoin() {
+        let change = Script::new([vec![0x00, 0x14], vec![0xCC; 20]].concat());
+        let receiver = Script::new([vec![0x00, 0x14], vec![0xDD; 20]].concat());
+        let tx = |inputs: u8, payment: u64| {
+            BitcoinTransaction::simple(
+                (1..=inputs)
+                    .map(|n| (OutPoint::new(dummy_txid(n), 0), 0xFFFFFFFD))
+                    .collect(),
+                vec![(31_337, change.clone()), (payment, receiver.clone())],
+            )
+        };
+        let known = [change.clone()];
+        // Receiver added a 73,421 sat input on top of a 100,000 sat payment.
+        assert!(tx(2, 173_421).looks_like_payjoin(&known));
+        assert!(!tx(2, 100_000).looks_like_payjoin(&known));
+        assert!(!tx(1, 173_421).looks_like_payjoin(&known));
+        assert!(!tx(2, 173_421).looks_like_payjoin(&[]));
+    }
+
+    #[test]
+    fn test_likely_change_output() {
+        let tx = |values: &[u64]| {
+            BitcoinTransaction::simple(
+                vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+                values.iter().map(|&v| (v, p2pkh_script())).collect(),
+            )
+        };
+        assert_eq!(tx(&[250_000, 1_734_219]).likely_change_output(), Some(1));
+        assert_eq!(tx(&[81_377, 5_000_000]).likely_change_output(), Some(0));
+        assert_eq!(tx(&[81_377, 64_219]).likely_change_output(), None);
+        assert_eq!(tx(&[10_000, 20_000]).likely_change_output(), None);
+        assert_eq!(tx(&[81_377]).likely_change_output(), None);
+    }
+
+    #[test]
+    fn test_is_likely_coinjoin() {
+        let tx = |inputs: u8| {
+            let values = [100_000, 100_000, 100_000, 3_127, 8_950];
+            BitcoinTransaction::simple(
+                (1..=inputs)
+                    .map(|n| (OutPoint::new(dummy_txid(n), 0), 0xFFFFFFFF))
+                    .collect(),
+                values
+                    .iter()
+                    .map(|&v| (v, Script::new(vec![0x51])))
+                    .collect(),
+            )
+        };
+        assert!(tx(3).is_likely_coinjoin(3));
+        assert!(!tx(3).is_likely_coinjoin(4));
+        assert!(!tx(2).is_likely_coinjoin(3));
+    }
+
+    #[test]
+    fn test_net_effect_for() {
+        let mine = p2pkh_script();
+        let theirs = Script::new([vec![0x00, 0x14], vec![0xDD; 20]].concat());
+        let watched: std::collections::HashSet<Vec<u8>> = [mine.to_vec()].into_iter().collect();
+        let tx = BitcoinTransaction::simple(
+            vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+            vec![(70_000, theirs.clone()), (25_000, mine.clone())],
+        );
+
+        let incoming = [TransactionOutput::new(100_000, theirs)];
+        assert_eq!(tx.net_effect_for(&watched, &incoming), 25_000);
+
+        let outgoing = [TransactionOutput::new(100_000, mine)];
+        assert_eq!(tx.net_effect_for(&watched, &outgoing), -75_000);
+    }
+
+    #[test]
+    fn test_deterministic_transaction() {
+        let tx = BitcoinTransaction::deterministic(42, 3, 2);
+        assert_eq!(tx, BitcoinTransaction::deterministic(42, 3, 2));
+        assert_ne!(tx, BitcoinTransaction::deterministic(43, 3, 2));
+        assert_eq!(tx.inputs.len(), 3);
+        assert_eq!(tx.outputs.len(), 2);
+        assert!(
+            tx.outputs
+                .iter()
+                .all(|o| o.script_pubkey.script_type() == ScriptType::P2wpkh)
+        );
+
+        let bytes = tx.to_bytes();
+        let (parsed, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, tx);
+        assert_eq!(consumed, bytes.len());
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_spent_outputs_of() {
+        let parent = genesis_coinbase();
+        let mut txid = parent.txid().0;
+        txid.reverse();
+        assert_eq!(
+            hex::encode(txid),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+
+        let child = BitcoinTransaction::simple(
+            vec![
+                (OutPoint::new(parent.txid().0, 0), 0xFFFFFFFF),
+                (OutPoint::new(parent.txid().0, 5), 0xFFFFFFFF),
+            ],
+            vec![(49_000, p2pkh_script())],
+        );
+        let unrelated = BitcoinTransaction::simple(
+            vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+            vec![(1_000, p2pkh_script())],
+        );
+        assert_eq!(
+            spent_outputs_of(&parent, &[unrelated, child.clone()]),
+            vec![(0, child.txid())]
+        );
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_fingerprint() {
+        let tx = genesis_coinbase();
+        let mut display = tx.txid().0;
+        display.reverse();
+        assert_eq!(tx.fingerprint(), hex::encode(display)[..8]);
+        assert_eq!(tx.fingerprint(), "4a5e1e4b");
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_stable_id() {
+        let tx = genesis_coinbase();
+        assert_eq!(tx.stable_id(), tx.txid().0);
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_sighash_legacy() {
+        let tx = BitcoinTransaction::simple(
+            vec![
+                (OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF),
+                (OutPoint::new(dummy_txid(2), 1), 0xFFFFFFFF),
+            ],
+            vec![(50_000, p2pkh_script())],
+        );
+        let code = p2pkh_script();
+
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert_eq!(tx.sighash_legacy(1, &code, 0x03), Ok(one));
+        assert_eq!(tx.sighash_legacy(1, &code, 0x83), Ok(one));
+        assert_ne!(tx.sighash_legacy(0, &code, 0x03), Ok(one));
+        assert_eq!(
+            tx.sighash_legacy(2, &code, 0x01),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        let mut repaid = tx.clone();
+        repaid.outputs[0].value = 40_000;
+        assert_ne!(
+            tx.sighash_legacy(0, &code, 0x01),
+            repaid.sighash_legacy(0, &code, 0x01)
+        );
+        assert_eq!(
+            tx.sighash_legacy(0, &code, 0x02),
+            repaid.sighash_legacy(0, &code, 0x02)
+        );
+
+        let mut other_input = tx.clone();
+        other_input.inputs[1].previous_output.vout = 7;
+        assert_ne!(
+            tx.sighash_legacy(0, &code, 0x01),
+            other_input.sighash_legacy(0, &code, 0x01)
+        );
+        assert_eq!(
+            tx.sighash_legacy(0, &code, 0x81),
+            other_input.sighash_legacy(0, &code, 0x81)
+        );
+
+        let mut separated = vec![0xAB];
+        separated.extend_from_slice(&code);
+        assert_eq!(
+            tx.sighash_legacy(0, &Script::new(separated), 0x01),
+            tx.sighash_legacy(0, &code, 0x01)
+        );
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_sighash_taproot() {
+        // Taproot sighash vectors from Bitcoin Core's test framework.
+        let check = |tx_hex: &str,
+                     prevouts_hex: &str,
+                     input_index: usize,
+                     sighash_type: u8,
+                     annex: Option<&str>,
+                     leaf_hash: Option<[u8; 32]>,
+                     expected: &str| {
+            let tx = BitcoinTransaction::from_bytes(&hex::decode(tx_hex).unwrap())
+                .unwrap()
+                .0;
+            let bytes = hex::decode(prevouts_hex).unwrap();
+            let (count, mut offset) = CompactSize::from_bytes(&bytes).unwrap();
+            let prevouts: Vec<TransactionOutput> = (0..count.value)
+                .map(|_| {
+                    let (output, consumed) =
+                        TransactionOutput::from_bytes(&bytes[offset..]).unwrap();
+                    offset += consumed;
+                    output
+                })
+                .collect();
+            let annex = annex.map(|annex| hex::decode(annex).unwrap());
+            let hash = tx
+                .sighash_taproot(
+                    input_index,
+                    &prevouts,
+                    sighash_type,
+                    annex.as_deref(),
+                    leaf_hash,
+                )
+                .unwrap();
+            assert_eq!(hex::encode(hash), expected);
+        };
+
+        check(
+            concat!(
+                "020000000164eb050a5e3da0c2a65e4786f26d753b7bc69691fabccafb11f7acef36641f18460100",
+                "00003101b2b404392a22000000000017a9147f2bde86fe78bf68a0544a4f290e12f0b7e0a08c8758",
+                "0200000000000017a91425d11723074ecfb96a0a83c3956bfaf362ae0c9087580200000000000016",
+                "00147e20f938993641de67bb0cdd71682aa34c4d29ad5802000000000000160014c64984dc8761ac",
+                "fa99418bd6bedc79b9287d652d72000000",
+            ),
+            concat!(
+                "01365724000000000023542156b39dab4f8f3508e0432cfb41fab110170acaa2d4c42539cb90a4dc",
+                "7c093bc500",
+            ),
+            0,
+            0x00,
+            None,
+            None,
+            "33ca0ebfb4a945eeee9569fc0f5040221275f88690b7f8592ada88ce3bdf6703",
+        );
+        check(
+            concat!(
+                "0200000002fff49be59befe7566050737910f6ccdc5e749c7f8860ddc140386463d88c5ad0f30000",
+                "00002cf68eb4a3d67f9d4c079249f7e4f27b8854815cb1ed13842d4fbf395f9e217fd605ee240901",
+                "00000065235d9203f458520000000000160014b6d48333bb13b4c644e57c43a9a26df3a44b785e58",
+                "020000000000001976a914eea9461a9e1e3f765d3af3e726162e0229fe3eb688ac58020000000000",
+                "001976a9143a8869c9f2b5ea1d4ff3aeeb6a8fb2fffb1ad5fe88ac0ad7125c",
+            ),
+            concat!(
+                "02591f220000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea1582748",
+                "06ff4ece48fb310000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea1",
+                "58274806ff4ece",
+            ),
+            1,
+            0x01,
+            None,
+            None,
+            "626ab955d58c9a8a600a0c580549d06dc7da4e802eb2a531f62a588e430967a8",
+        );
+        check(
+            concat!(
+                "0200000001350005f65aa830ced2079df348e2d8c2bdb4f10e2dde6a161d8a07b40d1ad87dae0000",
+                "00001611d0d603d9dc0e000000000017a914459b6d7d6bbb4d8837b4bf7e9a4556f952da2f5c8758",
+                "020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88ac58020000000000",
+                "001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88aca71c1f4f",
+            ),
+            concat!(
+                "01c4811000000000002251201bf9297d0a2968ae6693aadd0fa514717afefd218087a239afb7418e",
+                "2d22e65c",
+            ),
+            0,
+            0x81,
+            None,
+            None,
+            "dfa9437f9c9a1d1f9af271f79f2f5482f287cdb0d2e03fa92c8a9b216cc6061c",
+        );
+        check(
+            concat!(
+                "020000000185bed1a6da2bffbd60ec681a1bfb71c5111d6395b99b3f8b2bf90167111bcb18f50100",
+                "00007c83ace802ded24a00000000001600142c4698f9f7a773866879755aa78c516fb332af8e5802",
+                "000000000000160014d38639dfbac4259323b98a472405db0c461b31fa61073747",
+            ),
+            concat!(
+                "0144c84d0000000000225120e3f2107989c88e67296ab2faca930efa2e3a5bd3ff0904835a11c9e8",
+                "07458621",
+            ),
+            0,
+            0x02,
+            None,
+            None,
+            "3129de36a5d05fff97ffca31eb75fcccbbbc27b3147a7a36a9e4b45d8b625067",
+        );
+        check(
+            concat!(
+                "eb93dbb901028c8515589dac980b6e7f8e4088b77ed866ca0d6d210a7218b6fd0f6b22dd6d730000",
+                "0000eb4740a9047efc0e0000000000160014913da2128d8fcf292b3691db0e187414aa1783825802",
+                "000000000000160014913da2128d8fcf292b3691db0e187414aa178382580200000000000017a914",
+                "3dd27f01c6f7ef9bb9159937b17f17065ed01a0c875802000000000000160014d7630e19df70ada9",
+                "905ede1722b800c0005f246641000000",
+            ),
+            concat!(
+                "013fed110000000000225120eb536ae8c33580290630fc495046e998086a64f8f33b93b07967d902",
+                "9b265c55",
+            ),
+            0,
+            0x82,
+            None,
+            None,
+            "2441e8b0e063a2083ee790f14f2045022f07258ddde5ee01de543c9e789d80ae",
+        );
+        check(
+            concat!(
+                "02000000017836b409a5fed32211407e44b971591f2032053f14701fb5b3a30c0ff382f2cc9c0100",
+                "000061ac55f60288fb5600000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088",
+                "ac58020000000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ace4000000",
+            ),
+            concat!(
+                "01efa558000000000022512007071ea3dc7e331b0687d0193d1e6d6ed10e645ef36f10ef8831d5e5",
+                "22ac9e80",
+            ),
+            0,
+            0x03,
+            None,
+            None,
+            "30239345177cadd0e3ea413d49803580abb6cb27971b481b7788a78d35117a88",
+        );
+        check(
+            concat!(
+                "0100000001aa6deae89d5e0aaca58714fc76ef6f3c8284224888089232d4e663843ed3ab3eae0100",
+                "00008b6657a60450cb4c0000000000160014a3d42b5413ef0c0701c4702f3cd7d4df222c14705802",
+                "0000000000001976a91430b4ed8723a4ee8992aa2c8814cfe5c3ad0ab9d988ac5802000000000000",
+                "160014365b1166a6ed0a5e8e9dff17a6d00bbb43454bc758020000000000001976a914bc98c51a84",
+                "fe7fad5dc380eb8b39586eff47241688ac4f313247",
+            ),
+            concat!(
+                "0107af4e00000000002251202c36d243dfc06cb56a248e62df27ecba7417307511a81ae61aa41c59",
+                "7a929c69",
+            ),
+            0,
+            0x83,
+            None,
+            None,
+            "bf9c83f26c6dd16449e4921f813f551c4218e86f2ec906ca8611175b41b566df",
+        );
+        check(
+            concat!(
+                "0200000001df8123752e8f37d132c4e9f1ff7e4f9b986ade9211267e9ebd5fd22a5e718dec6d0100",
+                "0000ce4023b903cb7b23000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd78758",
+                "0200000000000017a914afd0d512a2c5c2b40e25669e9cc460303c325b8b87580200000000000017",
+                "a914a18b36ea7a094db2f4940fc09edf154e86de7bd787f6020000",
+            ),
+            concat!(
+                "01ea49260000000000225120ab5e9800806bf18cb246edcf5fe63441208fe955a4b5a35bbff65f5d",
+                "b622a010",
+            ),
+            0,
+            0x83,
+            Some(concat!(
+                "507b979802e62d397acb29f56743a791894b99372872fc5af06a4f6e8d242d0615cda53062bb20e6",
+                "ec79756fe39183f0c128adfe85559a8fa042b042c018aa8010143799e44f0893c40e1e",
+            )),
+            None,
+            "3b003000add359a364a156e73e02846782a59d0d95ca8c4638aaad99f2ef915c",
+        );
+        check(
+            concat!(
+                "020000000189fc651483f9296b906455dd939813bf086b1bbe7c77635e157c8e14ae290621950100",
+                "00004445b5c7044561320000000000160014331414dbdada7fb578f700f38fb69995fc9b5ab95802",
+                "0000000000001976a914268db0a8104cc6d8afd91233cc8b3d1ace8ac3ef88ac5802000000000000",
+                "17a914ec00dcb368d6a693e11986d265f659d2f59e8be2875802000000000000160014c715799a49",
+                "a0bae3956df9c17cb4440a673ac0df6f010000",
+            ),
+            concat!(
+                "011bec34000000000022512028055142ea437db73382e991861446040b61dd2185c4891d7daf6893",
+                "d79f7182",
+            ),
+            0,
+            0x01,
+            None,
+            Some(
+                Script::new(
+                    hex::decode(
+                        "20cc4e1107aea1d170c5ff5b6817e1303010049724fb3caa7941792ea9d29b3e2bacab",
+                    )
+                    .unwrap(),
+                )
+                .tapleaf_hash(0xC0),
+            ),
+            "d66de5274a60400c7b08c86ba6b7f198f40660079edf53aca89d2a9501317f2e",
+        );
+        check(
+            concat!(
+                "020000000189fc651483f9296b906455dd939813bf086b1bbe7c77635e157c8e14ae290621950100",
+                "00004445b5c7044561320000000000160014331414dbdada7fb578f700f38fb69995fc9b5ab95802",
+                "0000000000001976a914268db0a8104cc6d8afd91233cc8b3d1ace8ac3ef88ac5802000000000000",
+                "17a914ec00dcb368d6a693e11986d265f659d2f59e8be2875802000000000000160014c715799a49",
+                "a0bae3956df9c17cb4440a673ac0df6f010000",
+            ),
+            concat!(
+                "011bec34000000000022512028055142ea437db73382e991861446040b61dd2185c4891d7daf6893",
+                "d79f7182",
+            ),
+            0,
+            0x01,
+            None,
+            Some(
+                hex::decode("15a2530514e399f8b5cf0b3d3112cf5b289eaa3e308ba2071b58392fdc6da68a")
+                    .unwrap()
+                    .try_into()
+                    .unwrap(),
+            ),
+            "d66de5274a60400c7b08c86ba6b7f198f40660079edf53aca89d2a9501317f2e",
+        );
+
+        let tx = BitcoinTransaction::simple(
+            vec![(OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFF)],
+            Vec::new(),
+        );
+        let prevouts = [TransactionOutput::new(1_000, Script::new(vec![0x51, 0x20]))];
+        assert_eq!(
+            tx.sighash_taproot(0, &prevouts, 0x03, None, None),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            tx.sighash_taproot(0, &prevouts, 0x04, None, None),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            tx.sighash_taproot(0, &[], 0x00, None, None),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            tx.sighash_taproot(0, &prevouts, 0x00, Some(&[0x51]), None),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert!(tx.sighash_taproot(0, &prevouts, 0x00, None, None).is_ok());
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_for_txid_serialization() {
+        use sha2::{Digest, Sha256};
+        let tx = genesis_coinbase();
+        let hash: [u8; 32] = Sha256::digest(Sha256::digest(tx.for_txid_serialization())).into();
+        assert_eq!(Txid(hash), tx.txid());
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_tx_hasher() {
+        let tx = BitcoinTransaction::deterministic(7, 2, 3);
+        let mut hasher = TxHasher::new();
+        hasher.update_version(tx.version);
+        hasher.update_input_count(tx.inputs.len());
+        for input in &tx.inputs {
+            hasher.update_input(input);
+        }
+        hasher.update_output_count(tx.outputs.len());
+        for output in &tx.outputs {
+            hasher.update_output(output);
+        }
+        hasher.update_lock_time(tx.lock_time);
+        assert_eq!(hasher.finalize(), tx.txid());
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_block_header_meets_target() {
+        let genesis = hex::decode(concat!(
+            "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd",
+            "7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c",
+        ))
+        .unwrap();
+        let (mut header, _) = BlockHeader::from_bytes(&genesis).unwrap();
+        assert_eq!(header.bits, 0x1D00_FFFF);
+        let mut hash = header.block_hash();
+        hash.reverse();
+        assert_eq!(
+            hex::encode(hash),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert!(header.meets_target());
+
+        header.nonce += 1;
+        assert!(!header.meets_target());
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_verify_block_merkle() {
+        use sha2::{Digest, Sha256};
+        let sha256d = |data: &[u8]| -> [u8; 32] { Sha256::digest(Sha256::digest(data)).into() };
+
+        let coinbase = genesis_coinbase();
+        let genesis = BlockHeader::new(
+            1,
+            [0u8; 32],
+            coinbase.txid().0,
+            1231006505,
+            0x1D00_FFFF,
+            2083236893,
+        );
+        assert_eq!(
+            hex::encode(genesis.merkle_root),
+            "3ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a"
+        );
+        assert!(verify_block_merkle(
+            &genesis,
+            std::slice::from_ref(&coinbase)
+        ));
+
+        // Three transactions: the last txid is paired with itself.
+        let txs: Vec<_> = (1..=3)
+            .map(|n| BitcoinTransaction::deterministic(n, 1, 1))
+            .collect();
+        let ids: Vec<[u8; 32]> = txs.iter().map(|tx| tx.txid().0).collect();
+        let root = sha256d(
+            &[
+                sha256d(&[ids[0], ids[1]].concat()),
+                sha256d(&[ids[2], ids[2]].concat()),
+            ]
+            .concat(),
+        );
+        let header = BlockHeader::new(1, [0u8; 32], root, 0, 0x1D00_FFFF, 0);
+        assert!(verify_block_merkle(&header, &txs));
+
+        let mut tampered = txs.clone();
+        tampered[1].lock_time = 1;
+        assert!(!verify_block_merkle(&header, &tampered));
+        assert!(!verify_block_merkle(&header, &[]));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_script_to_address() {
+        let address = |script_hex: &str, network: Network| {
+            Script::new(hex::decode(script_hex).unwrap()).to_address(network)
+        };
+        let cases = [
+            (
+                "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+                Network::Bitcoin,
+                "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+            ),
+            (
+                "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+                Network::Testnet,
+                "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r",
+            ),
+            (
+                "a914da1745e9b549bd0bfa1a569971c77eba30cd5a4b87",
+                Network::Bitcoin,
+                "3MaB7QVq3k4pQx3BhsvEADgzQonLSBwMdj",
+            ),
+            (
+                "a914da1745e9b549bd0bfa1a569971c77eba30cd5a4b87",
+                Network::Signet,
+                "2ND8PB9RrfCaAcjfjP1Y6nAgFd9zWHYX4DN",
+            ),
+            (
+                "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+                Network::Bitcoin,
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            ),
+            (
+                "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+                Network::Regtest,
+                "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+            ),
+            (
+                "00204ae81572f06e1b88fd5ced7a1a000945432e83e1551e6f721ee9c00b8cc33260",
+                Network::Testnet,
+                "tb1qft5p2uhsdcdc3l2ua4ap5qqfg4pjaqlp250x7us7a8qqhrxrxfsqaqh7jw",
+            ),
+            (
+                "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                Network::Bitcoin,
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            ),
+            (
+                "5210751e76e8199196d454941c45d1b3a323",
+                Network::Bitcoin,
+                "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs",
+            ),
+        ];
+        for (script_hex, network, expected) in cases {
+            assert_eq!(address(script_hex, network).as_deref(), Some(expected));
+        }
+        assert_eq!(address("6a0401020304", Network::Bitcoin), None);
+        assert_eq!(
+            address(&format!("21{}ac", "02".repeat(33)), Network::Bitcoin),
+            None
+        );
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_explain() {
+        let p2wpkh =
+            Script::new(hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap());
+        let tx = BitcoinTransaction::simple(
+            vec![
+                (OutPoint::new(dummy_txid(1), 0), 0xFFFFFFFD),
+                (OutPoint::new(dummy_txid(2), 1), 0xFFFFFFFD),
+            ],
+            vec![(10_000_000, p2wpkh), (4_987_654, p2pkh_script())],
+        );
+        let prevouts = [
+            TransactionOutput::new(5_000_000, p2pkh_script()),
+            TransactionOutput::new(10_000_000, p2pkh_script()),
+        ];
+
+        let explanation = tx.explain(Some(&prevouts), Network::Bitcoin);
+        assert_eq!(
+            explanation.lines().collect::<Vec<_>>(),
+            vec![
+                "Spends 2 inputs totaling 0.15000000 BTC",
+                "Pays 0.10000000 BTC to bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                "Pays 0.04987654 BTC to 1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH (likely change)",
+                &format!(
+                    "Fee: 12346 sat ({:.2} sat/vB)",
+                    12_346.0 / tx.vsize() as f64
+                ),
+            ]
+        );
+
+        let without_prevouts = tx.explain(None, Network::Testnet);
+        assert!(without_prevouts.starts_with("Spends 2 inputs\n"));
+        assert!(without_prevouts.contains("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"));
+        assert!(!without_prevouts.contains("Fee"));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_cpfp_child() {
+        let parent = BitcoinTransaction::deterministic(9, 1, 2);
+        let output = parent.outputs[1].clone();
+        let change = Script::new(vec![0x51]);
+
+        let child = parent
+            .cpfp_child(1, &output, 1_000, change.clone())
+            .unwrap();
+        assert_eq!(
+            child.inputs[0].previous_output,
+            OutPoint::new(parent.txid().0, 1)
+        );
+        assert_eq!(
+            child.outputs,
+            vec![TransactionOutput::new(output.value - 1_000, change.clone())]
+        );
+
+        assert_eq!(
+            parent.cpfp_child(0, &output, 1_000, change.clone()),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            parent.cpfp_child(1, &output, output.value, change),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
 }